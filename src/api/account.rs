@@ -0,0 +1,386 @@
+use crate::api::{json_error, map_db_err, user_authentication, AppState};
+use crate::orm_entities::prelude::{Account, AccountProject, Project};
+use crate::orm_entities::sea_orm_active_enums::Role;
+use crate::orm_entities::{account, account_project, project};
+use actix_web::http::StatusCode;
+use actix_web::{post, web, HttpRequest, HttpResponse};
+use actix_web_httpauth::extractors::basic::BasicAuth;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use sea_orm::ActiveValue::Set;
+use sea_orm::{
+    ActiveEnum, ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, DbErr,
+    EntityTrait, NotSet, PaginatorTrait, QueryFilter, QueryOrder, TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+pub fn hash_password(password: &str) -> Result<String, DbErr> {
+    let salt = SaltString::generate(&mut rand::rngs::OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|x| x.to_string())
+        .map_err(|e| DbErr::Custom(format!("password hash error: {}", e)))
+}
+
+pub fn verify_password(password: &str, password_hash: &str) -> bool {
+    PasswordHash::new(password_hash)
+        .map(|hash| {
+            Argon2::default()
+                .verify_password(password.as_bytes(), &hash)
+                .is_ok()
+        })
+        .unwrap_or(false)
+}
+
+// 密码缓存的有效期及最大条目数
+const AUTH_CACHE_TTL: Duration = Duration::from_secs(600);
+const AUTH_CACHE_SIZE: usize = 1024;
+
+/// 验证通过的密码缓存, 避免每次请求都计算 Argon2
+///
+/// 以进程启动时随机生成的密钥对 (密码哈希, 密码) 计算 HMAC 作为键, 内存中不保留可离线暴力破解的密码摘要;
+/// 修改密码后密码哈希改变, 原有的缓存自然失效
+pub struct AuthCache {
+    secret: [u8; 32],
+    entries: HashMap<[u8; 32], Instant>,
+}
+
+impl Default for AuthCache {
+    fn default() -> Self {
+        Self {
+            secret: rand::random(),
+            entries: HashMap::new(),
+        }
+    }
+}
+
+impl AuthCache {
+    pub fn key(&self, password_hash: &str, password: &str) -> [u8; 32] {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.secret).expect("any key length");
+        mac.update(password_hash.as_bytes());
+        mac.update(&[0]);
+        mac.update(password.as_bytes());
+        mac.finalize().into_bytes().into()
+    }
+
+    pub fn contains(&mut self, key: &[u8; 32]) -> bool {
+        match self.entries.get(key) {
+            Some(time) if time.elapsed() < AUTH_CACHE_TTL => true,
+            Some(_) => {
+                self.entries.remove(key);
+                false
+            }
+            None => false,
+        }
+    }
+
+    pub fn insert(&mut self, key: [u8; 32]) {
+        if self.entries.len() >= AUTH_CACHE_SIZE {
+            self.entries
+                .retain(|_, time| time.elapsed() < AUTH_CACHE_TTL);
+        }
+        if self.entries.len() >= AUTH_CACHE_SIZE {
+            self.entries.clear();
+        }
+        self.entries.insert(key, Instant::now());
+    }
+}
+
+/// 解析角色名 `viewer` / `developer` / `admin`
+pub fn parse_role(role: &str) -> Option<Role> {
+    Role::try_from_value(&role.to_lowercase()).ok()
+}
+
+/// 创建账号, `password_hash` 为 `hash_password` 的结果
+pub async fn create_account(
+    db: &DatabaseConnection,
+    username: &str,
+    password_hash: String,
+    role: Role,
+    projects: &[i32],
+) -> Result<account::Model, DbErr> {
+    let txn = db.begin().await?;
+    let account = account::ActiveModel {
+        id: NotSet,
+        username: Set(username.to_string()),
+        password_hash: Set(password_hash),
+        role: Set(role),
+        disabled: Set(false),
+        create_time: Set(Utc::now().naive_utc()),
+    }
+    .insert(&txn)
+    .await?;
+    set_account_projects(&txn, account.id, projects).await?;
+    txn.commit().await?;
+    Ok(account)
+}
+
+/// 覆盖账号可访问的项目列表
+pub async fn set_account_projects<C: ConnectionTrait>(
+    db: &C,
+    account_id: i32,
+    projects: &[i32],
+) -> Result<(), DbErr> {
+    let mut projects = projects.to_vec();
+    projects.sort_unstable();
+    projects.dedup();
+    let found = Project::find()
+        .filter(project::Column::Id.is_in(projects.clone()))
+        .count(db)
+        .await?;
+    if found as usize != projects.len() {
+        return Err(DbErr::RecordNotFound(format!(
+            "some of projects {:?} do not exist",
+            projects
+        )));
+    }
+
+    AccountProject::delete_many()
+        .filter(account_project::Column::AccountId.eq(account_id))
+        .exec(db)
+        .await?;
+    if !projects.is_empty() {
+        AccountProject::insert_many(projects.into_iter().map(|project_id| {
+            account_project::ActiveModel {
+                id: NotSet,
+                account_id: Set(account_id),
+                project_id: Set(project_id),
+            }
+        }))
+        .exec(db)
+        .await?;
+    }
+    Ok(())
+}
+
+/// 账号是否为唯一启用的管理员, 停用或降级该账号后将无法再管理账号
+pub async fn is_last_admin<C: ConnectionTrait>(
+    db: &C,
+    account: &account::Model,
+) -> Result<bool, DbErr> {
+    if account.role != Role::Admin || account.disabled {
+        return Ok(false);
+    }
+    let others = Account::find()
+        .filter(account::Column::Role.eq(Role::Admin))
+        .filter(account::Column::Disabled.eq(false))
+        .filter(account::Column::Id.ne(account.id))
+        .count(db)
+        .await?;
+    Ok(others == 0)
+}
+
+/// 账号接口的数据库错误, 项目不存在时返回 400
+fn map_account_err(err: DbErr) -> actix_web::Error {
+    match err {
+        DbErr::RecordNotFound(message) => json_error(StatusCode::BAD_REQUEST, &message),
+        err => map_db_err(err),
+    }
+}
+
+pub async fn find_account(
+    db: &DatabaseConnection,
+    username: &str,
+) -> Result<Option<account::Model>, DbErr> {
+    Account::find()
+        .filter(account::Column::Username.eq(username))
+        .one(db)
+        .await
+}
+
+#[derive(Serialize, Debug)]
+struct AccountItemData {
+    id: i32,
+    username: String,
+    role: Role,
+    disabled: bool,
+    projects: Vec<i32>,
+    create_time: i64,
+}
+
+#[post("/api/account_list")]
+pub async fn api_account_list(
+    req: HttpRequest,
+    credentials: BasicAuth,
+    app_data: web::Data<AppState>,
+) -> actix_web::Result<HttpResponse> {
+    if !user_authentication(&req, &credentials, &app_data)
+        .await?
+        .is_admin()
+    {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let accounts = Account::find()
+        .order_by_asc(account::Column::Id)
+        .find_with_related(AccountProject)
+        .all(app_data.db_pool.get().unwrap())
+        .await
+        .map_err(map_db_err)?;
+
+    let items: Vec<AccountItemData> = accounts
+        .into_iter()
+        .map(|(account, projects)| AccountItemData {
+            id: account.id,
+            username: account.username,
+            role: account.role,
+            disabled: account.disabled,
+            projects: projects.into_iter().map(|x| x.project_id).collect(),
+            create_time: account.create_time.and_utc().timestamp(),
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(items))
+}
+
+#[derive(Deserialize, Debug)]
+struct AccountCreateRequestData {
+    username: String,
+    password: String,
+    role: Role,
+    #[serde(default)]
+    projects: Vec<i32>,
+}
+
+#[post("/api/account_create")]
+pub async fn api_account_create(
+    req: HttpRequest,
+    credentials: BasicAuth,
+    app_data: web::Data<AppState>,
+    json_data: web::Json<AccountCreateRequestData>,
+) -> actix_web::Result<HttpResponse> {
+    if !user_authentication(&req, &credentials, &app_data)
+        .await?
+        .is_admin()
+    {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let username = json_data.username.trim();
+    if username.is_empty() || json_data.password.is_empty() {
+        return Err(json_error(
+            StatusCode::BAD_REQUEST,
+            "username and password are required",
+        ));
+    }
+
+    let db = app_data.db_pool.get().unwrap();
+    if find_account(db, username)
+        .await
+        .map_err(map_db_err)?
+        .is_some()
+    {
+        return Err(json_error(StatusCode::CONFLICT, "username already exists"));
+    }
+
+    let password = json_data.password.clone();
+    let password_hash = web::block(move || hash_password(&password))
+        .await?
+        .map_err(map_db_err)?;
+    let account = create_account(
+        db,
+        username,
+        password_hash,
+        json_data.role,
+        &json_data.projects,
+    )
+    .await
+    .map_err(map_account_err)?;
+
+    Ok(HttpResponse::Ok().json(AccountItemData {
+        id: account.id,
+        username: account.username,
+        role: account.role,
+        disabled: account.disabled,
+        projects: json_data.projects.clone(),
+        create_time: account.create_time.and_utc().timestamp(),
+    }))
+}
+
+#[derive(Deserialize, Debug)]
+struct AccountUpdateRequestData {
+    id: i32,
+    // 以下字段为空时保持不变
+    #[serde(default)]
+    role: Option<Role>,
+    #[serde(default)]
+    disabled: Option<bool>,
+    #[serde(default)]
+    password: Option<String>,
+    #[serde(default)]
+    projects: Option<Vec<i32>>,
+}
+
+#[post("/api/account_update")]
+pub async fn api_account_update(
+    req: HttpRequest,
+    credentials: BasicAuth,
+    app_data: web::Data<AppState>,
+    json_data: web::Json<AccountUpdateRequestData>,
+) -> actix_web::Result<HttpResponse> {
+    if !user_authentication(&req, &credentials, &app_data)
+        .await?
+        .is_admin()
+    {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let password_hash = match &json_data.password {
+        Some(password) if password.is_empty() => {
+            return Err(json_error(StatusCode::BAD_REQUEST, "password is empty"));
+        }
+        Some(password) => {
+            let password = password.clone();
+            Some(
+                web::block(move || hash_password(&password))
+                    .await?
+                    .map_err(map_db_err)?,
+            )
+        }
+        None => None,
+    };
+
+    let db = app_data.db_pool.get().unwrap();
+    let txn = db.begin().await.map_err(map_db_err)?;
+    let Some(account) = Account::find_by_id(json_data.id)
+        .one(&txn)
+        .await
+        .map_err(map_db_err)?
+    else {
+        return Err(json_error(StatusCode::NOT_FOUND, "account not found"));
+    };
+
+    let demoted = json_data.role.is_some_and(|x| x != Role::Admin);
+    let disabled = json_data.disabled == Some(true);
+    if (demoted || disabled) && is_last_admin(&txn, &account).await.map_err(map_db_err)? {
+        return Err(json_error(
+            StatusCode::CONFLICT,
+            "cannot demote or disable the last enabled admin",
+        ));
+    }
+
+    let mut account: account::ActiveModel = account.into();
+    if let Some(role) = json_data.role {
+        account.role = Set(role);
+    }
+    if let Some(disabled) = json_data.disabled {
+        account.disabled = Set(disabled);
+    }
+    if let Some(password_hash) = password_hash {
+        account.password_hash = Set(password_hash);
+    }
+    let account = account.update(&txn).await.map_err(map_db_err)?;
+    if let Some(projects) = &json_data.projects {
+        set_account_projects(&txn, account.id, projects)
+            .await
+            .map_err(map_account_err)?;
+    }
+    txn.commit().await.map_err(map_db_err)?;
+
+    Ok(HttpResponse::Ok().body("{\"data\": \"ok\"}"))
+}
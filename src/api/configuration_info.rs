@@ -0,0 +1,43 @@
+use serde_json::{Map, Value};
+
+/// 解析客户端上报的配置信息
+///
+/// 支持 JSON 对象以及旧版 `{supports_ETC1: true, max_texture_size: 4096}` 格式,
+/// 旧版格式逐项按 `key: value` 解析, 与 `show_support.py` 的规则一致;
+/// 布尔值和整数转换为对应类型, 其余保留为字符串
+pub fn parse_configuration_info(info: &str) -> Map<String, Value> {
+    if let Ok(Value::Object(result)) = serde_json::from_str(info) {
+        return result;
+    }
+
+    let mut result = Map::new();
+
+    let cleaned = info.trim_matches(|c: char| c == '{' || c == '}' || c.is_whitespace());
+    for item in cleaned.split([',', '\n']) {
+        let Some((key, value)) = item.split_once(':') else {
+            continue;
+        };
+        let key = key.trim();
+        if key.is_empty()
+            || !key
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '_' || c == '.')
+        {
+            continue;
+        }
+
+        let value = value.trim();
+        let value = if value.eq_ignore_ascii_case("true") {
+            Value::Bool(true)
+        } else if value.eq_ignore_ascii_case("false") {
+            Value::Bool(false)
+        } else if let Ok(x) = value.parse::<u64>() {
+            Value::from(x)
+        } else {
+            Value::String(value.to_string())
+        };
+        result.insert(key.to_string(), value);
+    }
+
+    result
+}
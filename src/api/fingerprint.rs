@@ -0,0 +1,657 @@
+use crate::orm_entities::normalize_rule;
+use crate::orm_entities::prelude::NormalizeRule;
+use regex::Regex;
+use sea_orm::{ColumnTrait, ConnectionTrait, DbErr, EntityTrait, QueryFilter, QueryOrder};
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+/// 参与指纹计算的应用内栈帧数量
+pub const MAX_FINGERPRINT_FRAMES: usize = 5;
+
+/// 没有调用栈标题时至少需要的栈帧行数, 单行普通消息也可能恰好符合栈帧格式
+const MIN_HEADLESS_FRAMES: usize = 2;
+
+/// 解析后的栈帧
+#[derive(Debug, Clone)]
+pub struct Frame {
+    /// 函数名, 无法识别时为空
+    pub function: String,
+    /// 文件或模块路径
+    pub file: String,
+    /// 是否属于应用自身代码
+    pub in_app: bool,
+}
+
+/// 解析后的调用栈
+#[derive(Debug, Default)]
+pub struct Stack {
+    /// 异常类型, 例如 `TypeError`
+    pub kind: Option<String>,
+    pub frames: Vec<Frame>,
+}
+
+impl Stack {
+    /// 有调用栈标题或栈帧行数足够时才视为调用栈, 否则返回空栈
+    fn confirmed(self, has_header: bool) -> Self {
+        if has_header || self.frames.len() >= MIN_HEADLESS_FRAMES {
+            self
+        } else {
+            Self::default()
+        }
+    }
+}
+
+/// 调用栈解析器, 无法识别时返回空栈
+pub trait StackParser: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn parse(&self, message: &str) -> Stack;
+}
+
+/// 错误分组指纹
+#[derive(Debug)]
+pub struct Fingerprint {
+    /// 写入 upload_log.hash 的值
+    pub hash: String,
+    /// 分组依据: fingerprint / 解析器名称 / message
+    pub method: &'static str,
+    /// 参与哈希计算的内容
+    pub components: Vec<String>,
+}
+
+/// 编译后的归一化规则
+struct CompiledRule {
+    regex: Regex,
+    replacement: String,
+}
+
+/// 错误分组指纹计算流程
+///
+/// 依次尝试: 上报时指定的指纹, 各调用栈解析器得到的应用内栈帧, 归一化后的消息内容;
+/// 栈帧的函数名及文件同样按归一化规则处理
+pub struct Fingerprinter {
+    parsers: Vec<Box<dyn StackParser>>,
+    /// 按项目缓存的归一化规则, 项目 0 的规则对所有项目生效
+    rules: RwLock<HashMap<i32, Vec<CompiledRule>>>,
+}
+
+impl Default for Fingerprinter {
+    fn default() -> Self {
+        Self::new(vec![
+            Box::new(JsStackParser::new()),
+            Box::new(LuaStackParser::new()),
+            Box::new(NativeStackParser::new()),
+        ])
+    }
+}
+
+impl Fingerprinter {
+    pub fn new(parsers: Vec<Box<dyn StackParser>>) -> Self {
+        Self {
+            parsers,
+            rules: Default::default(),
+        }
+    }
+
+    /// 从数据库重新加载并编译已启用的归一化规则, 规则变更后调用
+    pub async fn reload_rules<C: ConnectionTrait>(&self, db: &C) -> Result<(), DbErr> {
+        let models = NormalizeRule::find()
+            .filter(normalize_rule::Column::Enabled.eq(true))
+            .order_by_asc(normalize_rule::Column::ProjectId)
+            .order_by_asc(normalize_rule::Column::Position)
+            .order_by_asc(normalize_rule::Column::Id)
+            .all(db)
+            .await?;
+
+        let mut rules: HashMap<i32, Vec<CompiledRule>> = HashMap::new();
+        for model in models {
+            // 规则在写入时已校验, 这里跳过手工修改数据库导致的无效规则
+            match Regex::new(&model.pattern) {
+                Ok(regex) => rules
+                    .entry(model.project_id)
+                    .or_default()
+                    .push(CompiledRule {
+                        regex,
+                        replacement: model.replacement,
+                    }),
+                Err(err) => eprintln!("invalid normalize rule {}: {}", model.id, err),
+            }
+        }
+
+        *self.rules.write().unwrap() = rules;
+        Ok(())
+    }
+
+    /// 依次应用全局规则及项目规则
+    pub fn normalize_error_message(&self, project_id: i32, error_msg: &str) -> String {
+        let rules = self.rules.read().unwrap();
+        let mut normalized = error_msg.to_string();
+
+        let mut project_ids = vec![0];
+        if project_id != 0 {
+            project_ids.push(project_id);
+        }
+        for rule in project_ids
+            .into_iter()
+            .filter_map(|x| rules.get(&x))
+            .flatten()
+        {
+            normalized = rule
+                .regex
+                .replace_all(&normalized, rule.replacement.as_str())
+                .into_owned();
+        }
+
+        normalized
+    }
+
+    /// 对栈帧的函数名及文件应用归一化规则, 去掉其中的 id、地址及临时路径等
+    fn normalize_frame(&self, project_id: i32, frame: &Frame) -> Frame {
+        Frame {
+            function: self.normalize_error_message(project_id, &frame.function),
+            file: self.normalize_error_message(project_id, &frame.file),
+            in_app: frame.in_app,
+        }
+    }
+
+    /// 计算分组指纹, 其中 hash 写入 upload_log.hash
+    pub fn fingerprint(
+        &self,
+        project_id: i32,
+        log_type: &str,
+        message: &str,
+        explicit: Option<&str>,
+    ) -> Fingerprint {
+        if let Some(explicit) = explicit.map(str::trim).filter(|x| !x.is_empty()) {
+            return Fingerprint {
+                hash: digest(project_id, &format!("fingerprint:{}", explicit), log_type),
+                method: "fingerprint",
+                components: vec![explicit.to_string()],
+            };
+        }
+
+        for parser in &self.parsers {
+            let stack = parser.parse(message);
+            if stack.frames.is_empty() {
+                continue;
+            }
+
+            // 优先使用应用内栈帧, 全部为系统栈帧时退回到全部栈帧
+            let in_app = stack.frames.iter().filter(|x| x.in_app).collect::<Vec<_>>();
+            let frames = if in_app.is_empty() {
+                stack.frames.iter().collect()
+            } else {
+                in_app
+            };
+
+            let mut components = Vec::with_capacity(MAX_FINGERPRINT_FRAMES + 1);
+            if let Some(kind) = stack.kind {
+                components.push(kind);
+            }
+            components.extend(
+                frames
+                    .into_iter()
+                    .take(MAX_FINGERPRINT_FRAMES)
+                    .map(|frame| frame_key(&self.normalize_frame(project_id, frame))),
+            );
+
+            return Fingerprint {
+                hash: digest(
+                    project_id,
+                    &format!("{}:{}", parser.name(), components.join("\n")),
+                    log_type,
+                ),
+                method: parser.name(),
+                components,
+            };
+        }
+
+        // 未识别出调用栈, 保持旧版按消息内容计算的哈希
+        let normalized = self.normalize_error_message(project_id, message);
+        Fingerprint {
+            hash: digest(project_id, &normalized, log_type),
+            method: "message",
+            components: vec![normalized],
+        }
+    }
+}
+
+/// 计算哈希值, 未指定项目时保持旧版格式不变
+fn digest(project_id: i32, content: &str, log_type: &str) -> String {
+    let digest = if project_id == 0 {
+        md5::compute(format!("{}-{}", content, log_type))
+    } else {
+        md5::compute(format!("{}-{}-{}", project_id, content, log_type))
+    };
+    format!("{:x}", digest)
+}
+
+/// 栈帧参与哈希的内容, 只保留函数名和文件名, 忽略行号及目录
+pub fn frame_key(frame: &Frame) -> String {
+    let file = frame
+        .file
+        .split(['?', '#'])
+        .next()
+        .unwrap_or_default()
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or_default();
+    // 去掉打包产物文件名中的内容哈希, 例如 app.3f2a1b9c.js
+    let file = match file.split('.').collect::<Vec<_>>().as_slice() {
+        [name, hash, ext] if hash.len() >= 6 && hash.chars().all(|c| c.is_ascii_hexdigit()) => {
+            format!("{}.{}", name, ext)
+        }
+        _ => file.to_string(),
+    };
+
+    if frame.function.is_empty() {
+        file
+    } else {
+        format!("{}@{}", frame.function, file)
+    }
+}
+
+/// JavaScript 调用栈, 支持 V8 (`at func (file:1:2)`) 及 Firefox/Safari (`func@file:1:2`) 格式
+pub struct JsStackParser {
+    v8_regex: Regex,
+    gecko_regex: Regex,
+    kind_regex: Regex,
+}
+
+impl JsStackParser {
+    pub fn new() -> Self {
+        Self {
+            v8_regex: Regex::new(r"^\s*at (?:(?:async )?(.+?) \()?(.+?):\d+(?::\d+)?\)?\s*$")
+                .unwrap(),
+            gecko_regex: Regex::new(r"^\s*([^@\s]*)@(.+?):\d+(?::\d+)?\s*$").unwrap(),
+            kind_regex: Regex::new(r"^(?:Uncaught )?([A-Z]\w*(?:Error|Exception))\b").unwrap(),
+        }
+    }
+}
+
+impl StackParser for JsStackParser {
+    fn name(&self) -> &'static str {
+        "javascript"
+    }
+
+    fn parse(&self, message: &str) -> Stack {
+        let mut stack = Stack {
+            kind: self
+                .kind_regex
+                .captures(message.trim_start())
+                .map(|x| x[1].to_string()),
+            frames: vec![],
+        };
+
+        for line in message.lines() {
+            let Some(captures) = self
+                .v8_regex
+                .captures(line)
+                .or_else(|| self.gecko_regex.captures(line))
+            else {
+                continue;
+            };
+
+            let function = captures.get(1).map_or("", |x| x.as_str());
+            let function = if function == "<anonymous>" {
+                ""
+            } else {
+                function
+            };
+            let file = captures[2].to_string();
+            let in_app = !(file.contains("node_modules")
+                || file.starts_with("node:")
+                || file.starts_with("internal/")
+                || file == "<anonymous>"
+                || file == "native");
+
+            stack.frames.push(Frame {
+                function: function.to_string(),
+                file,
+                in_app,
+            });
+        }
+
+        // 栈帧格式只匹配整行, 识别出异常类型时第一行是错误标题
+        let has_header = stack.kind.is_some();
+        stack.confirmed(has_header)
+    }
+}
+
+/// Lua 调用栈, 解析 `stack traceback:` 之后的内容
+pub struct LuaStackParser {
+    frame_regex: Regex,
+    name_regex: Regex,
+}
+
+impl LuaStackParser {
+    pub fn new() -> Self {
+        Self {
+            frame_regex: Regex::new(r"^\s*(\[C\]|[^:\s][^:]*?):(?:\d+:)? in (.+?)\s*$").unwrap(),
+            name_regex: Regex::new(r"['`]([^']+)'").unwrap(),
+        }
+    }
+}
+
+impl StackParser for LuaStackParser {
+    fn name(&self) -> &'static str {
+        "lua"
+    }
+
+    fn parse(&self, message: &str) -> Stack {
+        let mut stack = Stack::default();
+        let Some((_, traceback)) = message.split_once("stack traceback:") else {
+            return stack;
+        };
+
+        for line in traceback.lines() {
+            let Some(captures) = self.frame_regex.captures(line) else {
+                continue;
+            };
+
+            let file = captures[1].to_string();
+            let location = &captures[2];
+            // function <file:line> 为匿名函数, 不使用行号
+            let function = if let Some(name) = self.name_regex.captures(location) {
+                name[1].to_string()
+            } else if location == "main chunk" {
+                location.to_string()
+            } else {
+                String::new()
+            };
+
+            stack.frames.push(Frame {
+                function,
+                in_app: file != "[C]" && file != "?",
+                file,
+            });
+        }
+
+        stack
+    }
+}
+
+/// 原生调用栈, 支持 gdb、Android tombstone、Apple crash report 及 Windows 模块格式
+pub struct NativeStackParser {
+    header_regex: Regex,
+    gdb_regex: Regex,
+    tombstone_regex: Regex,
+    apple_regex: Regex,
+    windows_regex: Regex,
+}
+
+impl NativeStackParser {
+    pub fn new() -> Self {
+        Self {
+            header_regex: Regex::new(
+                r"(?m)^\s*(?:backtrace:|Thread \d+(?: Crashed)?:|Last Exception Backtrace:|Stack trace:|Call stack:)",
+            )
+            .unwrap(),
+            gdb_regex: Regex::new(
+                r"^\s*#\d+\s+(?:0x[0-9a-fA-F]+ in )?(\S.*?)(?: \(.*\))? (?:at|from) (\S+?)(?::\d+)?\s*$",
+            )
+            .unwrap(),
+            tombstone_regex: Regex::new(
+                r"^\s*#\d+ pc [0-9a-fA-F]+\s+(\S+)(?: \(offset 0x[0-9a-fA-F]+\))?(?: \((.*?)(?:\+\d+)?\)(?:\s|$))?",
+            )
+            .unwrap(),
+            apple_regex: Regex::new(
+                r"^\s*\d+\s+(\S+)\s+0x[0-9a-fA-F]+\s+(.+?)(?: \+ \d+)?\s*$",
+            )
+            .unwrap(),
+            windows_regex: Regex::new(
+                r"^\s*(?:\d+\s+)?([\w.\-]+\.(?:exe|dll))!(\S+?)(?:\+0x[0-9a-fA-F]+)?(?:\s|$)",
+            )
+            .unwrap(),
+        }
+    }
+
+    fn is_system_module(file: &str) -> bool {
+        const SYSTEM_PATHS: [&str; 6] = [
+            "/system/", "/apex/", "/vendor/", "/lib/", "/lib64/", "/usr/",
+        ];
+        const SYSTEM_MODULES: [&str; 14] = [
+            "libc.so",
+            "libc++",
+            "libart.so",
+            "libdl.so",
+            "libsystem_",
+            "libdyld",
+            "libobjc",
+            "CoreFoundation",
+            "UIKitCore",
+            "Foundation",
+            "GraphicsServices",
+            "ntdll.dll",
+            "kernel32.dll",
+            "KERNELBASE.dll",
+        ];
+
+        let module = file.rsplit(['/', '\\']).next().unwrap_or_default();
+        SYSTEM_PATHS.iter().any(|x| file.starts_with(x))
+            || SYSTEM_MODULES.iter().any(|x| module.starts_with(x))
+    }
+}
+
+impl StackParser for NativeStackParser {
+    fn name(&self) -> &'static str {
+        "native"
+    }
+
+    fn parse(&self, message: &str) -> Stack {
+        let mut stack = Stack::default();
+
+        for line in message.lines() {
+            let (function, file) = if let Some(x) = self.tombstone_regex.captures(line) {
+                (x.get(2).map_or("", |x| x.as_str()), x[1].to_string())
+            } else if let Some(x) = self.gdb_regex.captures(line) {
+                (x.get(1).map_or("", |x| x.as_str()), x[2].to_string())
+            } else if let Some(x) = self.windows_regex.captures(line) {
+                (x.get(2).map_or("", |x| x.as_str()), x[1].to_string())
+            } else if let Some(x) = self.apple_regex.captures(line) {
+                (x.get(2).map_or("", |x| x.as_str()), x[1].to_string())
+            } else {
+                continue;
+            };
+
+            // 没有符号时只剩地址, 不参与哈希
+            let function = if function == "??" || function.starts_with("0x") {
+                ""
+            } else {
+                function
+            };
+
+            stack.frames.push(Frame {
+                function: function.to_string(),
+                in_app: !Self::is_system_module(&file),
+                file,
+            });
+        }
+
+        stack.confirmed(self.header_regex.is_match(message))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(stack: &Stack) -> Vec<String> {
+        stack.frames.iter().map(frame_key).collect()
+    }
+
+    fn in_app(stack: &Stack) -> Vec<bool> {
+        stack.frames.iter().map(|x| x.in_app).collect()
+    }
+
+    #[test]
+    fn parses_v8_stack() {
+        let stack = JsStackParser::new().parse(
+            "TypeError: Cannot read properties of undefined (reading 'x')
+    at render (https://cdn.example.com/assets/app.3f2a1b9c.js:10:5)
+    at async Object.run (webpack:///src/main.js:3:1)
+    at <anonymous> (node_modules/react/index.js:1:1)
+    at node:internal/main:12:3",
+        );
+        assert_eq!(stack.kind.as_deref(), Some("TypeError"));
+        assert_eq!(
+            keys(&stack),
+            ["render@app.js", "Object.run@main.js", "index.js", "main"]
+        );
+        assert_eq!(in_app(&stack), [true, true, false, false]);
+    }
+
+    #[test]
+    fn parses_gecko_stack() {
+        let stack = JsStackParser::new().parse(
+            "render@https://example.com/js/app.js:10:5
+@https://example.com/js/app.js:20:1",
+        );
+        assert_eq!(stack.kind, None);
+        assert_eq!(keys(&stack), ["render@app.js", "app.js"]);
+    }
+
+    #[test]
+    fn accepts_single_js_frame_after_error_header() {
+        let stack = JsStackParser::new().parse(
+            "ReferenceError: foo is not defined
+    at https://example.com/app.js:1:1",
+        );
+        assert_eq!(keys(&stack), ["app.js"]);
+    }
+
+    #[test]
+    fn parses_lua_traceback() {
+        let stack = LuaStackParser::new().parse(
+            "main.lua:10: attempt to index a nil value
+stack traceback:
+\t[C]: in function 'error'
+\tscripts/player.lua:10: in function 'update'
+\tmain.lua:20: in main chunk
+\t[C]: ?",
+        );
+        assert_eq!(
+            keys(&stack),
+            ["error@[C]", "update@player.lua", "main chunk@main.lua"]
+        );
+        assert_eq!(in_app(&stack), [false, true, true]);
+    }
+
+    #[test]
+    fn parses_apple_crash_report() {
+        let stack = NativeStackParser::new().parse(
+            "Thread 0 Crashed:
+0   libsystem_kernel.dylib        0x00000001c0b2a0c4 __pthread_kill + 8
+1   MyApp                         0x0000000104a8c123 -[ViewController crash] + 44
+2   UIKitCore                     0x0000000189d7a2c0 0x189d00000 + 500416",
+        );
+        assert_eq!(
+            keys(&stack),
+            [
+                "__pthread_kill@libsystem_kernel.dylib",
+                "-[ViewController crash]@MyApp",
+                "UIKitCore"
+            ]
+        );
+        assert_eq!(in_app(&stack), [false, true, false]);
+    }
+
+    #[test]
+    fn parses_android_tombstone() {
+        let stack = NativeStackParser::new().parse(
+            "signal 6 (SIGABRT), code -1 (SI_QUEUE)
+backtrace:
+    #00 pc 000000000001e3c4  /system/lib64/libc.so (abort+164)
+    #01 pc 0000000000012345  /data/app/com.example/lib/arm64/libgame.so (Game::update()+32)
+    #02 pc 0000000000054321  /data/app/com.example/lib/arm64/libgame.so (offset 0x1000)",
+        );
+        assert_eq!(
+            keys(&stack),
+            ["abort@libc.so", "Game::update()@libgame.so", "libgame.so"]
+        );
+        assert_eq!(in_app(&stack), [false, true, true]);
+    }
+
+    #[test]
+    fn parses_gdb_and_windows_frames() {
+        let parser = NativeStackParser::new();
+        let stack = parser.parse(
+            "#0  0x00007f1c2a in crash_handler (sig=11) at src/main.c:42
+#1  0x00007f1c2b in __libc_start_main () from /lib64/libc.so.6",
+        );
+        assert_eq!(
+            keys(&stack),
+            ["crash_handler@main.c", "__libc_start_main@libc.so.6"]
+        );
+        assert_eq!(in_app(&stack), [true, false]);
+
+        let stack = parser.parse(
+            "game.exe!Game::Update+0x1f
+ntdll.dll!RtlUserThreadStart+0x21",
+        );
+        assert_eq!(
+            keys(&stack),
+            ["Game::Update@game.exe", "RtlUserThreadStart@ntdll.dll"]
+        );
+        assert_eq!(in_app(&stack), [true, false]);
+    }
+
+    #[test]
+    fn plain_messages_are_not_stacks() {
+        let js = JsStackParser::new();
+        let lua = LuaStackParser::new();
+        let native = NativeStackParser::new();
+        for message in [
+            "1 item 0xdead failed",
+            "user@host:8080",
+            "connect to db@10.0.0.1:5432 failed",
+            "main.lua:10: in function 'update'",
+            "#3 retry in 5 seconds at server.rs",
+            "failed to load config\nretrying",
+        ] {
+            assert!(js.parse(message).frames.is_empty(), "{}", message);
+            assert!(lua.parse(message).frames.is_empty(), "{}", message);
+            assert!(native.parse(message).frames.is_empty(), "{}", message);
+        }
+
+        let fingerprint = Fingerprinter::default().fingerprint(0, "error", "user@host:8080", None);
+        assert_eq!(fingerprint.method, "message");
+    }
+
+    /// 栈帧中只有归一化规则替换的内容不同时分为同一组
+    #[test]
+    fn normalize_rules_apply_to_frames() {
+        let fingerprinter = Fingerprinter::default();
+        fingerprinter.rules.write().unwrap().insert(
+            0,
+            vec![
+                CompiledRule {
+                    regex: Regex::new(r"_\d+").unwrap(),
+                    replacement: "_<id>".into(),
+                },
+                CompiledRule {
+                    regex: Regex::new(r"/tmp/[^/]+/").unwrap(),
+                    replacement: "/tmp/<dir>/".into(),
+                },
+            ],
+        );
+
+        let stack = |id: u32, dir: &str| {
+            format!(
+                "TypeError: failed
+    at handler_{id} (/tmp/{dir}/app.js:10:5)
+    at main (/srv/app/main.js:3:1)"
+            )
+        };
+        let a = fingerprinter.fingerprint(0, "error", &stack(12345, "a1b2"), None);
+        let b = fingerprinter.fingerprint(0, "error", &stack(67890, "c3d4"), None);
+        assert_eq!(a.method, "javascript");
+        assert_eq!(
+            a.components,
+            ["TypeError", "handler_<id>@app.js", "main@main.js"]
+        );
+        assert_eq!(a.hash, b.hash);
+
+        let c = Fingerprinter::default().fingerprint(0, "error", &stack(67890, "c3d4"), None);
+        assert_ne!(a.hash, c.hash);
+    }
+}
@@ -0,0 +1,308 @@
+use crate::api::{json_error, map_db_err, user_authentication, AppState};
+use crate::orm_entities::normalize_rule;
+use crate::orm_entities::prelude::{NormalizeRule, Project, UploadLog};
+use crate::orm_entities::upload_log;
+use actix_web::http::StatusCode;
+use actix_web::{post, web, HttpRequest, HttpResponse};
+use actix_web_httpauth::extractors::basic::BasicAuth;
+use regex::Regex;
+use sea_orm::ActiveValue::Set;
+use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, NotSet, QueryFilter, QueryOrder};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Debug)]
+struct NormalizeRuleItemData {
+    id: i32,
+    project_id: i32,
+    position: i32,
+    pattern: String,
+    replacement: String,
+    enabled: bool,
+}
+
+impl From<normalize_rule::Model> for NormalizeRuleItemData {
+    fn from(model: normalize_rule::Model) -> Self {
+        Self {
+            id: model.id,
+            project_id: model.project_id,
+            position: model.position,
+            pattern: model.pattern,
+            replacement: model.replacement,
+            enabled: model.enabled,
+        }
+    }
+}
+
+fn check_pattern(pattern: &str) -> actix_web::Result<()> {
+    if pattern.is_empty() {
+        return Err(json_error(StatusCode::BAD_REQUEST, "pattern is empty"));
+    }
+    Regex::new(pattern)
+        .map(|_| ())
+        .map_err(|err| json_error(StatusCode::BAD_REQUEST, &err.to_string()))
+}
+
+#[derive(Deserialize, Debug)]
+struct NormalizeRuleListRequestData {
+    #[serde(default)]
+    project_id: i32,
+}
+
+/// 项目的归一化规则, 按执行顺序排列
+#[post("/api/normalize_rule_list")]
+pub async fn api_normalize_rule_list(
+    req: HttpRequest,
+    credentials: BasicAuth,
+    app_data: web::Data<AppState>,
+    json_data: web::Json<NormalizeRuleListRequestData>,
+) -> actix_web::Result<HttpResponse> {
+    if !user_authentication(&req, &credentials, &app_data)
+        .await?
+        .is_admin()
+    {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let rules = NormalizeRule::find()
+        .filter(normalize_rule::Column::ProjectId.eq(json_data.project_id))
+        .order_by_asc(normalize_rule::Column::Position)
+        .order_by_asc(normalize_rule::Column::Id)
+        .all(app_data.db_pool.get().unwrap())
+        .await
+        .map_err(map_db_err)?;
+
+    let items: Vec<NormalizeRuleItemData> = rules.into_iter().map(Into::into).collect();
+    Ok(HttpResponse::Ok().json(items))
+}
+
+#[derive(Deserialize, Debug)]
+struct NormalizeRuleCreateRequestData {
+    #[serde(default)]
+    project_id: i32,
+    pattern: String,
+    replacement: String,
+    // 执行顺序, 为空时追加到最后
+    position: Option<i32>,
+    #[serde(default = "default_enabled")]
+    enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+#[post("/api/normalize_rule_create")]
+pub async fn api_normalize_rule_create(
+    req: HttpRequest,
+    credentials: BasicAuth,
+    app_data: web::Data<AppState>,
+    json_data: web::Json<NormalizeRuleCreateRequestData>,
+) -> actix_web::Result<HttpResponse> {
+    if !user_authentication(&req, &credentials, &app_data)
+        .await?
+        .is_admin()
+    {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    check_pattern(&json_data.pattern)?;
+
+    let db = app_data.db_pool.get().unwrap();
+    if json_data.project_id != 0
+        && Project::find_by_id(json_data.project_id)
+            .one(db)
+            .await
+            .map_err(map_db_err)?
+            .is_none()
+    {
+        return Err(json_error(StatusCode::NOT_FOUND, "project not found"));
+    }
+
+    let position = match json_data.position {
+        Some(position) => position,
+        None => NormalizeRule::find()
+            .filter(normalize_rule::Column::ProjectId.eq(json_data.project_id))
+            .order_by_desc(normalize_rule::Column::Position)
+            .one(db)
+            .await
+            .map_err(map_db_err)?
+            .map_or(0, |x| x.position + 1),
+    };
+
+    let rule = normalize_rule::ActiveModel {
+        id: NotSet,
+        project_id: Set(json_data.project_id),
+        position: Set(position),
+        pattern: Set(json_data.pattern.to_owned()),
+        replacement: Set(json_data.replacement.to_owned()),
+        enabled: Set(json_data.enabled),
+    }
+    .insert(db)
+    .await
+    .map_err(map_db_err)?;
+
+    app_data
+        .fingerprinter
+        .reload_rules(db)
+        .await
+        .map_err(map_db_err)?;
+
+    Ok(HttpResponse::Ok().json(NormalizeRuleItemData::from(rule)))
+}
+
+#[derive(Deserialize, Debug)]
+struct NormalizeRuleUpdateRequestData {
+    id: i32,
+    pattern: Option<String>,
+    replacement: Option<String>,
+    position: Option<i32>,
+    enabled: Option<bool>,
+}
+
+#[post("/api/normalize_rule_update")]
+pub async fn api_normalize_rule_update(
+    req: HttpRequest,
+    credentials: BasicAuth,
+    app_data: web::Data<AppState>,
+    json_data: web::Json<NormalizeRuleUpdateRequestData>,
+) -> actix_web::Result<HttpResponse> {
+    if !user_authentication(&req, &credentials, &app_data)
+        .await?
+        .is_admin()
+    {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let db = app_data.db_pool.get().unwrap();
+    let Some(rule) = NormalizeRule::find_by_id(json_data.id)
+        .one(db)
+        .await
+        .map_err(map_db_err)?
+    else {
+        return Err(json_error(StatusCode::NOT_FOUND, "rule not found"));
+    };
+
+    let mut rule: normalize_rule::ActiveModel = rule.into();
+    if let Some(pattern) = &json_data.pattern {
+        check_pattern(pattern)?;
+        rule.pattern = Set(pattern.to_owned());
+    }
+    if let Some(replacement) = &json_data.replacement {
+        rule.replacement = Set(replacement.to_owned());
+    }
+    if let Some(position) = json_data.position {
+        rule.position = Set(position);
+    }
+    if let Some(enabled) = json_data.enabled {
+        rule.enabled = Set(enabled);
+    }
+    let rule = rule.update(db).await.map_err(map_db_err)?;
+
+    app_data
+        .fingerprinter
+        .reload_rules(db)
+        .await
+        .map_err(map_db_err)?;
+
+    Ok(HttpResponse::Ok().json(NormalizeRuleItemData::from(rule)))
+}
+
+#[derive(Deserialize, Debug)]
+struct NormalizeRuleRemoveRequestData {
+    id: i32,
+}
+
+#[post("/api/normalize_rule_remove")]
+pub async fn api_normalize_rule_remove(
+    req: HttpRequest,
+    credentials: BasicAuth,
+    app_data: web::Data<AppState>,
+    json_data: web::Json<NormalizeRuleRemoveRequestData>,
+) -> actix_web::Result<HttpResponse> {
+    if !user_authentication(&req, &credentials, &app_data)
+        .await?
+        .is_admin()
+    {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let db = app_data.db_pool.get().unwrap();
+    NormalizeRule::delete_by_id(json_data.id)
+        .exec(db)
+        .await
+        .map_err(map_db_err)?;
+
+    app_data
+        .fingerprinter
+        .reload_rules(db)
+        .await
+        .map_err(map_db_err)?;
+
+    Ok(HttpResponse::Ok().body("{\"data\": \"ok\"}"))
+}
+
+#[derive(Deserialize, Debug)]
+struct GroupingPreviewRequestData {
+    #[serde(default)]
+    project_id: i32,
+    #[serde(default = "default_log_type")]
+    log_type: String,
+    message: String,
+    fingerprint: Option<String>,
+}
+
+fn default_log_type() -> String {
+    "error".into()
+}
+
+#[derive(Serialize, Debug)]
+struct GroupingPreviewResponseData {
+    // 按当前规则归一化后的消息
+    normalized: String,
+    // 分组依据: fingerprint / javascript / lua / native / message
+    method: &'static str,
+    components: Vec<String>,
+    hash: String,
+    // 已存在的同组错误
+    log_id: Option<i32>,
+}
+
+/// 预览一条消息按当前规则的归一化及分组结果
+#[post("/api/grouping/preview")]
+pub async fn api_grouping_preview(
+    req: HttpRequest,
+    credentials: BasicAuth,
+    app_data: web::Data<AppState>,
+    json_data: web::Json<GroupingPreviewRequestData>,
+) -> actix_web::Result<HttpResponse> {
+    if !user_authentication(&req, &credentials, &app_data)
+        .await?
+        .is_admin()
+    {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let fingerprint = app_data.fingerprinter.fingerprint(
+        json_data.project_id,
+        &json_data.log_type,
+        &json_data.message,
+        json_data.fingerprint.as_deref(),
+    );
+
+    let log_id = UploadLog::find()
+        .filter(upload_log::Column::Hash.eq(&fingerprint.hash))
+        .one(app_data.db_pool.get().unwrap())
+        .await
+        .map_err(map_db_err)?
+        .map(|x| x.id);
+
+    Ok(HttpResponse::Ok().json(GroupingPreviewResponseData {
+        normalized: app_data
+            .fingerprinter
+            .normalize_error_message(json_data.project_id, &json_data.message),
+        method: fingerprint.method,
+        components: fingerprint.components,
+        hash: fingerprint.hash,
+        log_id,
+    }))
+}
@@ -0,0 +1,213 @@
+use crate::api::log::{record_logs, PendingLog};
+use crate::api::version::VersionSchemes;
+use crate::api::{user_authentication, AppState, SamplingPolicy};
+use actix_web::error::InternalError;
+use actix_web::http::header::RETRY_AFTER;
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use actix_web_httpauth::extractors::basic::BasicAuth;
+use chrono::Utc;
+use sea_orm::{DatabaseConnection, TransactionTrait};
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+// 单次写入的最大上报数量
+const MAX_FLUSH_SIZE: usize = 1000;
+
+/// 上报写入队列
+///
+/// 上报放入队列后立即返回, 由后台任务按间隔批量写入数据库, 同一哈希值的错误每次写入只更新一次
+#[derive(Clone)]
+pub struct IngestQueue {
+    sender: mpsc::Sender<PendingLog>,
+    stats: Arc<IngestStats>,
+}
+
+/// 写入队列服务启动以来的统计, 上报在写入前已经返回成功, 写入失败只能通过这里发现
+#[derive(Default)]
+struct IngestStats {
+    // 已写入的上报数
+    written: AtomicU64,
+    // 整批写入失败后逐条重试的批次数
+    failed_flushes: AtomicU64,
+    // 逐条重试仍失败而丢弃的上报数
+    dropped: AtomicU64,
+    // 队列已满被拒绝的上报数
+    rejected: AtomicU64,
+    // 最近一次写入失败的时间(unix 时间戳)及原因
+    last_error: Mutex<Option<(i64, String)>>,
+}
+
+impl IngestStats {
+    fn record_error(&self, err: &str) {
+        *self.last_error.lock().unwrap() = Some((Utc::now().timestamp(), err.to_string()));
+    }
+}
+
+impl IngestQueue {
+    /// 启动后台写入任务, 所有 `IngestQueue` 释放后任务写完剩余的上报并退出
+    pub fn start(
+        db: DatabaseConnection,
+        sampling: SamplingPolicy,
+        versions: Arc<VersionSchemes>,
+        capacity: usize,
+        flush_interval: Duration,
+    ) -> (Self, JoinHandle<()>) {
+        let (sender, receiver) = mpsc::channel(capacity);
+        let stats = Arc::new(IngestStats::default());
+        let handle = tokio::spawn(run_writer(
+            receiver,
+            db,
+            sampling,
+            versions,
+            flush_interval,
+            stats.clone(),
+        ));
+        (Self { sender, stats }, handle)
+    }
+
+    /// 放入队列, 队列已满时返回带 `Retry-After` 的 503 错误
+    pub fn push(&self, log: PendingLog) -> actix_web::Result<()> {
+        self.sender.try_send(log).map_err(|_| {
+            self.stats.rejected.fetch_add(1, Ordering::Relaxed);
+            InternalError::from_response(
+                "ingest queue is full",
+                HttpResponse::ServiceUnavailable()
+                    .insert_header((RETRY_AFTER, "1"))
+                    .json(serde_json::json!({ "error": "ingest queue is full" })),
+            )
+            .into()
+        })
+    }
+}
+
+async fn run_writer(
+    mut receiver: mpsc::Receiver<PendingLog>,
+    db: DatabaseConnection,
+    sampling: SamplingPolicy,
+    versions: Arc<VersionSchemes>,
+    flush_interval: Duration,
+    stats: Arc<IngestStats>,
+) {
+    let mut buffer = vec![];
+    while let Some(log) = receiver.recv().await {
+        buffer.push(log);
+
+        // 收集一个间隔内的上报后统一写入
+        let deadline = Instant::now() + flush_interval;
+        while buffer.len() < MAX_FLUSH_SIZE {
+            match tokio::time::timeout_at(deadline, receiver.recv()).await {
+                Ok(Some(log)) => buffer.push(log),
+                _ => break,
+            }
+        }
+
+        flush(&db, sampling, &versions, &stats, &buffer).await;
+        buffer.clear();
+    }
+}
+
+async fn flush(
+    db: &DatabaseConnection,
+    sampling: SamplingPolicy,
+    versions: &VersionSchemes,
+    stats: &IngestStats,
+    logs: &[PendingLog],
+) {
+    let result = async {
+        let txn = db.begin().await?;
+        record_logs(&txn, sampling, versions, logs).await?;
+        txn.commit().await
+    }
+    .await;
+
+    let Err(err) = result else {
+        stats
+            .written
+            .fetch_add(logs.len() as u64, Ordering::Relaxed);
+        return;
+    };
+
+    // 整批写入失败时逐条重试, 避免一条错误数据导致整批丢失
+    eprintln!(
+        "flush {} logs failed: {}, retry one by one",
+        logs.len(),
+        err
+    );
+    stats.failed_flushes.fetch_add(1, Ordering::Relaxed);
+    stats.record_error(&err.to_string());
+    for log in logs {
+        match record_logs(db, sampling, versions, std::slice::from_ref(log)).await {
+            Ok(()) => {
+                stats.written.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(err) => {
+                eprintln!("drop log of project {}: {}", log.project_id, err);
+                stats.dropped.fetch_add(1, Ordering::Relaxed);
+                stats.record_error(&err.to_string());
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct IngestQueueStatsResponseData {
+    // 是否开启了写入队列, 未开启时上报同步写入, 失败直接返回给客户端
+    enabled: bool,
+    capacity: usize,
+    // 队列中等待写入的上报数
+    pending: usize,
+    written: u64,
+    failed_flushes: u64,
+    dropped: u64,
+    rejected: u64,
+    last_error: Option<String>,
+    last_error_time: Option<i64>,
+}
+
+/// 服务启动以来写入队列的统计
+#[get("/api/ingest_queue/stats")]
+pub async fn api_ingest_queue_stats(
+    req: HttpRequest,
+    credentials: BasicAuth,
+    app_data: web::Data<AppState>,
+) -> actix_web::Result<HttpResponse> {
+    if !user_authentication(&req, &credentials, &app_data)
+        .await?
+        .is_admin()
+    {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let Some(queue) = &app_data.ingest_queue else {
+        return Ok(HttpResponse::Ok().json(IngestQueueStatsResponseData {
+            enabled: false,
+            capacity: 0,
+            pending: 0,
+            written: 0,
+            failed_flushes: 0,
+            dropped: 0,
+            rejected: 0,
+            last_error: None,
+            last_error_time: None,
+        }));
+    };
+
+    let stats = &queue.stats;
+    let last_error = stats.last_error.lock().unwrap().clone();
+    Ok(HttpResponse::Ok().json(IngestQueueStatsResponseData {
+        enabled: true,
+        capacity: queue.sender.max_capacity(),
+        pending: queue.sender.max_capacity() - queue.sender.capacity(),
+        written: stats.written.load(Ordering::Relaxed),
+        failed_flushes: stats.failed_flushes.load(Ordering::Relaxed),
+        dropped: stats.dropped.load(Ordering::Relaxed),
+        rejected: stats.rejected.load(Ordering::Relaxed),
+        last_error_time: last_error.as_ref().map(|(time, _)| *time),
+        last_error: last_error.map(|(_, err)| err),
+    }))
+}
@@ -0,0 +1,620 @@
+use crate::api::log::find_issue_by_hash;
+use crate::api::version::VersionSchemes;
+use crate::api::{json_error, map_db_err, user_authentication, AppState};
+use crate::orm_entities::prelude::{
+    LogPackageVersion, LogStatusChange, UploadOccurrence, UploadUser,
+};
+use crate::orm_entities::sea_orm_active_enums::IssueStatus;
+use crate::orm_entities::{
+    log_package_version, log_status_change, upload_log, upload_occurrence, upload_user,
+};
+use actix_web::http::StatusCode;
+use actix_web::{post, web, HttpRequest, HttpResponse};
+use actix_web_httpauth::extractors::basic::BasicAuth;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use sea_orm::sea_query::{Expr, OnConflict, SimpleExpr};
+use sea_orm::ActiveValue::Set;
+use sea_orm::{
+    ActiveEnum, ActiveModelTrait, ColumnTrait, Condition, ConnectionTrait, DbErr, EntityTrait,
+    NotSet, QueryFilter, QueryOrder, QuerySelect, TransactionTrait, Value,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+
+impl IssueStatus {
+    /// 是否为已解决的状态
+    pub fn is_resolved(self) -> bool {
+        matches!(self, Self::Resolved | Self::ResolvedInNextRelease)
+    }
+
+    /// 是否为忽略或静音的状态
+    pub fn is_silenced(self) -> bool {
+        matches!(
+            self,
+            Self::Ignored | Self::MutedUntilTime | Self::MutedUntilCount
+        )
+    }
+
+    /// 读取时的实际状态: 按时间静音且已到期的错误视为未解决,
+    /// 数据库中的状态要等到期后的下一次上报才会更新
+    pub fn effective(self, muted_until: Option<NaiveDateTime>, now: NaiveDateTime) -> Self {
+        match (self, muted_until) {
+            (Self::MutedUntilTime, Some(time)) if time > now => self,
+            (Self::MutedUntilTime, _) => Self::Unresolved,
+            _ => self,
+        }
+    }
+
+    /// 是否允许手动变更到 `next`
+    ///
+    /// 已解决的错误只能重新打开或修改解决方式; 未解决的错误不能重新打开;
+    /// 忽略/静音的错误取消时回到未解决, 也可以修改静音条件
+    pub fn can_change_to(self, next: Self) -> bool {
+        if self.is_resolved() {
+            next == Self::Reopened || next.is_resolved()
+        } else if self.is_silenced() {
+            next != Self::Reopened
+        } else {
+            !matches!(next, Self::Unresolved | Self::Reopened)
+        }
+    }
+
+    /// 合并多个错误时的状态: 全部相同时保持不变, 否则有复现的标记为复现, 其余为未解决
+    pub fn merge(statuses: impl IntoIterator<Item = Self>) -> Self {
+        let mut statuses = statuses.into_iter();
+        let Some(first) = statuses.next() else {
+            return Self::Unresolved;
+        };
+        statuses.fold(first, |result, status| match (result, status) {
+            (Self::Reopened, _) | (_, Self::Reopened) => Self::Reopened,
+            (result, status) if result == status => result,
+            _ => Self::Unresolved,
+        })
+    }
+}
+
+/// 实际状态为未解决的错误的查询条件, 包括静音已到期的错误, 见 [`IssueStatus::effective`]
+pub fn unresolved_condition(now: NaiveDateTime) -> Condition {
+    Condition::any()
+        .add(upload_log::Column::Status.eq(IssueStatus::Unresolved))
+        .add(
+            Condition::all()
+                .add(upload_log::Column::Status.eq(IssueStatus::MutedUntilTime))
+                .add(
+                    Condition::any()
+                        .add(upload_log::Column::MutedUntil.is_null())
+                        .add(upload_log::Column::MutedUntil.lte(now)),
+                ),
+        )
+}
+
+/// 记录错误各包名已上报的最新版本, 每条上报都会记录, 不受采样影响
+pub async fn record_package_versions<'a, C: ConnectionTrait>(
+    db: &C,
+    versions: &VersionSchemes,
+    log_id: i32,
+    reports: impl IntoIterator<Item = (&'a str, &'a str)>,
+) -> Result<(), DbErr> {
+    let mut latest: Vec<(&str, &str)> = vec![];
+    for (package, version) in reports {
+        let version = version.trim();
+        if version.is_empty() {
+            continue;
+        }
+        match latest.iter_mut().find(|(x, _)| *x == package) {
+            Some((_, x)) if versions.compare(package, version, x).is_gt() => *x = version,
+            Some(_) => {}
+            None => latest.push((package, version)),
+        }
+    }
+
+    for (package, version) in latest {
+        let row = LogPackageVersion::find()
+            .filter(log_package_version::Column::LogId.eq(log_id))
+            .filter(log_package_version::Column::Package.eq(package))
+            .one(db)
+            .await?;
+        match row {
+            Some(row)
+                if versions
+                    .compare(package, version, &row.latest_version)
+                    .is_gt() =>
+            {
+                LogPackageVersion::update_many()
+                    .col_expr(
+                        log_package_version::Column::LatestVersion,
+                        Expr::value(version),
+                    )
+                    .filter(log_package_version::Column::Id.eq(row.id))
+                    .exec(db)
+                    .await?;
+            }
+            Some(_) => {}
+            None => {
+                LogPackageVersion::insert(log_package_version::ActiveModel {
+                    id: NotSet,
+                    log_id: Set(log_id),
+                    package: Set(package.to_string()),
+                    latest_version: Set(version.to_string()),
+                    resolved_version: Set(None),
+                })
+                .on_conflict(
+                    OnConflict::columns([
+                        log_package_version::Column::LogId,
+                        log_package_version::Column::Package,
+                    ])
+                    .do_nothing()
+                    .to_owned(),
+                )
+                .exec_without_returning(db)
+                .await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 错误各包名解决的版本, 包名 -> 版本
+pub async fn find_resolved_versions<C: ConnectionTrait>(
+    db: &C,
+    log_id: i32,
+) -> Result<HashMap<String, String>, DbErr> {
+    Ok(LogPackageVersion::find()
+        .filter(log_package_version::Column::LogId.eq(log_id))
+        .all(db)
+        .await?
+        .into_iter()
+        .filter_map(|x| Some((x.package, x.resolved_version?)))
+        .collect())
+}
+
+/// 已解决的错误收到的上报中第一条视为复现的上报, 返回其 (包名, 版本)
+///
+/// 上报只与其包名的解决版本比较: 该版本及之后版本的上报才算复现(下个版本解决时为之后的版本),
+/// 旧版本客户端的上报不会重新打开错误; 包名没有解决版本时任何上报都算复现
+pub fn find_regression<'a>(
+    log: &upload_log::Model,
+    versions: &VersionSchemes,
+    resolved_versions: &HashMap<String, String>,
+    occurrences: impl IntoIterator<Item = (&'a str, &'a str)>,
+) -> Option<(&'a str, &'a str)> {
+    if !log.status.is_resolved() {
+        return None;
+    }
+    occurrences
+        .into_iter()
+        .find(|(package, version)| match resolved_versions.get(*package) {
+            Some(resolved_version) => {
+                let order = versions.compare(package, version, resolved_version);
+                match log.status {
+                    IssueStatus::ResolvedInNextRelease => order.is_gt(),
+                    _ => order.is_ge(),
+                }
+            }
+            None => true,
+        })
+}
+
+/// 新上报到达时错误状态的更新表达式
+///
+/// `regressed` 为根据解决版本判断的是否复现, `resolution_time` 为判断时的解决时间,
+/// 判断之后重新标记解决时不标记为复现;
+/// 静音到期或静音期间的上报次数达到上限时回到未解决; 其余状态不变.
+/// `count` 为本次累加的上报次数
+pub fn reopen_expr(count: i32, regressed: bool, resolution_time: NaiveDateTime) -> SimpleExpr {
+    let sql = format!(
+        "CASE \
+            WHEN status IN ({resolved}, {next_release}) AND ? AND resolution_time = ? THEN {reopened} \
+            WHEN status = {muted_until_time} AND (muted_until IS NULL OR muted_until <= ?) THEN {unresolved} \
+            WHEN status = {muted_until_count} AND (muted_until_count IS NULL OR total_count + ? >= muted_until_count) THEN {unresolved} \
+            ELSE status \
+        END",
+        resolved = IssueStatus::Resolved.to_value(),
+        next_release = IssueStatus::ResolvedInNextRelease.to_value(),
+        reopened = IssueStatus::Reopened.to_value(),
+        muted_until_time = IssueStatus::MutedUntilTime.to_value(),
+        muted_until_count = IssueStatus::MutedUntilCount.to_value(),
+        unresolved = IssueStatus::Unresolved.to_value(),
+    );
+
+    let values: Vec<Value> = vec![
+        regressed.into(),
+        resolution_time.into(),
+        Utc::now().naive_utc().into(),
+        count.into(),
+    ];
+    Expr::cust_with_values(sql, values)
+}
+
+/// 记录一次状态变更, `operator` 为空表示由上报自动变更
+pub async fn record_status_change<C: ConnectionTrait>(
+    db: &C,
+    log_id: i32,
+    from: IssueStatus,
+    to: IssueStatus,
+    reason: &str,
+    operator: &str,
+) -> Result<(), DbErr> {
+    log_status_change::ActiveModel {
+        id: NotSet,
+        log_id: Set(log_id),
+        from_status: Set(from),
+        to_status: Set(to),
+        reason: Set(reason.to_string()),
+        operator: Set(operator.to_string()),
+        time: Set(Utc::now().naive_utc()),
+    }
+    .insert(db)
+    .await?;
+    Ok(())
+}
+
+/// 变更后的状态及其条件
+pub struct StatusChange {
+    pub status: IssueStatus,
+    pub muted_until: Option<i64>,
+    pub mute_count: Option<i32>,
+    /// 解决的版本, 仅 status 为 resolved 时使用, 为空时任何上报都算复现
+    pub version: Option<String>,
+    /// 解决版本对应的包名, 为空时用于该错误上报过的所有包名
+    pub package: Option<String>,
+}
+
+/// 手动变更错误状态, 校验状态转换及静音条件后记录变更原因
+pub async fn change_status<C: ConnectionTrait + TransactionTrait>(
+    db: &C,
+    versions: &VersionSchemes,
+    log: upload_log::Model,
+    change: StatusChange,
+    reason: &str,
+    operator: &str,
+) -> actix_web::Result<()> {
+    let now = Utc::now().naive_utc();
+    let from = log.status.effective(log.muted_until, now);
+    let status = change.status;
+    if !from.can_change_to(status) {
+        return Err(json_error(
+            StatusCode::CONFLICT,
+            &format!(
+                "cannot change status from {} to {}",
+                json!(from),
+                json!(status)
+            ),
+        ));
+    }
+
+    let muted_until = match (status, change.muted_until) {
+        (IssueStatus::MutedUntilTime, Some(time)) => Some(
+            DateTime::from_timestamp(time, 0)
+                .map(|x| x.naive_utc())
+                .filter(|x| *x > now)
+                .ok_or_else(|| {
+                    json_error(StatusCode::BAD_REQUEST, "muted_until must be in the future")
+                })?,
+        ),
+        (IssueStatus::MutedUntilTime, None) => {
+            return Err(json_error(StatusCode::BAD_REQUEST, "missing muted_until"));
+        }
+        _ => None,
+    };
+    let muted_until_count = match (status, change.mute_count) {
+        (IssueStatus::MutedUntilCount, Some(count)) if count > 0 => {
+            Some(log.total_count.saturating_add(count))
+        }
+        (IssueStatus::MutedUntilCount, _) => {
+            return Err(json_error(
+                StatusCode::BAD_REQUEST,
+                "mute_count must be greater than 0",
+            ));
+        }
+        _ => None,
+    };
+
+    let resolved_version = match status {
+        IssueStatus::Resolved => change
+            .version
+            .map(|x| x.trim().to_string())
+            .filter(|x| !x.is_empty()),
+        _ => None,
+    };
+    let package = change
+        .package
+        .map(|x| x.trim().to_string())
+        .filter(|x| !x.is_empty());
+
+    let txn = db.begin().await.map_err(map_db_err)?;
+    let log_id = log.id;
+
+    // 以保存的上报详情补全各包名的最新版本, 拆分或取消合并的错误可能缺少记录
+    let reports = UploadUser::find()
+        .inner_join(UploadOccurrence)
+        .filter(upload_occurrence::Column::LogId.eq(log_id))
+        .select_only()
+        .column(upload_user::Column::Package)
+        .column(upload_user::Column::Version)
+        .distinct()
+        .into_tuple::<(String, String)>()
+        .all(&txn)
+        .await
+        .map_err(map_db_err)?;
+    let specified = package.as_deref().zip(resolved_version.as_deref());
+    record_package_versions(
+        &txn,
+        versions,
+        log_id,
+        reports
+            .iter()
+            .map(|(package, version)| (package.as_str(), version.as_str()))
+            .chain(specified),
+    )
+    .await
+    .map_err(map_db_err)?;
+
+    LogPackageVersion::update_many()
+        .col_expr(
+            log_package_version::Column::ResolvedVersion,
+            Expr::value(None::<String>),
+        )
+        .filter(log_package_version::Column::LogId.eq(log_id))
+        .exec(&txn)
+        .await
+        .map_err(map_db_err)?;
+    let resolved_version = match (status, resolved_version) {
+        (IssueStatus::Resolved, Some(version)) => Some(Expr::value(version)),
+        // 记录各包名当前已上报的最新版本, 之后只有更新版本的上报才算复现
+        (IssueStatus::ResolvedInNextRelease, _) => {
+            Some(Expr::col(log_package_version::Column::LatestVersion).into())
+        }
+        _ => None,
+    };
+    if let Some(resolved_version) = resolved_version {
+        let mut update = LogPackageVersion::update_many()
+            .col_expr(
+                log_package_version::Column::ResolvedVersion,
+                resolved_version,
+            )
+            .filter(log_package_version::Column::LogId.eq(log_id));
+        if let Some(package) = &package {
+            update = update.filter(log_package_version::Column::Package.eq(package));
+        }
+        update.exec(&txn).await.map_err(map_db_err)?;
+    }
+
+    let mut log: upload_log::ActiveModel = log.into();
+    log.status = Set(status);
+    log.muted_until = Set(muted_until);
+    log.muted_until_count = Set(muted_until_count);
+    if status.is_resolved() {
+        log.resolution_time = Set(now);
+    }
+    log.update(&txn).await.map_err(map_db_err)?;
+    record_status_change(&txn, log_id, from, status, reason, operator)
+        .await
+        .map_err(map_db_err)?;
+    txn.commit().await.map_err(map_db_err)?;
+
+    Ok(())
+}
+
+#[derive(Deserialize, Debug)]
+struct LogStatusRequestData {
+    hash: String,
+    status: IssueStatus,
+    // 变更原因
+    #[serde(default)]
+    reason: String,
+    // 静音截止时间(unix 时间戳), status 为 muted_until_time 时必填
+    #[serde(default)]
+    muted_until: Option<i64>,
+    // 静音期间允许的上报次数, status 为 muted_until_count 时必填
+    #[serde(default)]
+    mute_count: Option<i32>,
+    // 解决的版本, status 为 resolved 时可选
+    #[serde(default)]
+    version: Option<String>,
+    // 解决版本对应的包名, 为空时用于该错误上报过的所有包名
+    #[serde(default)]
+    package: Option<String>,
+}
+
+/// 变更错误状态
+#[post("/api/log_status")]
+pub async fn api_log_status(
+    req: HttpRequest,
+    credentials: BasicAuth,
+    app_data: web::Data<AppState>,
+    json_data: web::Json<LogStatusRequestData>,
+) -> actix_web::Result<HttpResponse> {
+    let user = user_authentication(&req, &credentials, &app_data).await?;
+    if !user.can_write() {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let db = app_data.db_pool.get().unwrap();
+    let Some(log) = find_issue_by_hash(db, &json_data.hash)
+        .await
+        .map_err(map_db_err)?
+        .filter(|x| user.can_view_project(x.project_id))
+    else {
+        return Err(json_error(StatusCode::NOT_FOUND, "log not found"));
+    };
+
+    change_status(
+        db,
+        &app_data.version_schemes,
+        log,
+        StatusChange {
+            status: json_data.status,
+            muted_until: json_data.muted_until,
+            mute_count: json_data.mute_count,
+            version: json_data.version.clone(),
+            package: json_data.package.clone(),
+        },
+        json_data.reason.trim(),
+        &user.username,
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().body("{\"data\": \"ok\"}"))
+}
+
+#[derive(Deserialize, Debug)]
+struct LogStatusHistoryRequestData {
+    hash: String,
+}
+
+#[derive(Serialize, Debug)]
+struct LogStatusHistoryItemData {
+    from_status: IssueStatus,
+    to_status: IssueStatus,
+    reason: String,
+    operator: String,
+    time: i64,
+}
+
+/// 错误的状态变更记录, 按时间倒序
+#[post("/api/log_status_history")]
+pub async fn api_log_status_history(
+    req: HttpRequest,
+    credentials: BasicAuth,
+    app_data: web::Data<AppState>,
+    json_data: web::Json<LogStatusHistoryRequestData>,
+) -> actix_web::Result<HttpResponse> {
+    let user = user_authentication(&req, &credentials, &app_data).await?;
+
+    let db = app_data.db_pool.get().unwrap();
+    let Some(log) = find_issue_by_hash(db, &json_data.hash)
+        .await
+        .map_err(map_db_err)?
+        .filter(|x| user.can_view_project(x.project_id))
+    else {
+        return Err(json_error(StatusCode::NOT_FOUND, "log not found"));
+    };
+
+    let items: Vec<LogStatusHistoryItemData> = LogStatusChange::find()
+        .filter(log_status_change::Column::LogId.eq(log.id))
+        .order_by_desc(log_status_change::Column::Id)
+        .all(db)
+        .await
+        .map_err(map_db_err)?
+        .into_iter()
+        .map(|x| LogStatusHistoryItemData {
+            from_status: x.from_status,
+            to_status: x.to_status,
+            reason: x.reason,
+            operator: x.operator,
+            time: x.time.and_utc().timestamp(),
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(items))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::migration::{Migrator, MigratorTrait};
+    use crate::orm_entities::prelude::UploadLog;
+    use chrono::Duration;
+    use sea_orm::{ConnectOptions, Database, PaginatorTrait};
+
+    /// 按时间静音的错误到期后即视为未解决, 不需要等待新的上报
+    #[test]
+    fn muted_until_time_expires_at_read_time() {
+        let now = Utc::now().naive_utc();
+        let later = now + Duration::minutes(5);
+        let status = IssueStatus::MutedUntilTime;
+
+        assert_eq!(status.effective(Some(later), now), status);
+        assert_eq!(
+            status.effective(Some(later), later),
+            IssueStatus::Unresolved
+        );
+        assert_eq!(
+            status.effective(Some(later), later + Duration::seconds(1)),
+            IssueStatus::Unresolved
+        );
+        assert_eq!(status.effective(None, now), IssueStatus::Unresolved);
+        for status in [
+            IssueStatus::Ignored,
+            IssueStatus::MutedUntilCount,
+            IssueStatus::Resolved,
+        ] {
+            assert_eq!(status.effective(Some(now), later), status);
+        }
+    }
+
+    /// 未解决数量的查询条件与读取时的实际状态一致
+    #[tokio::test]
+    async fn unresolved_condition_matches_expired_mutes() {
+        let path =
+            std::env::temp_dir().join(format!("tiny-http-test-{:016x}.db", rand::random::<u64>()));
+        let mut opt = ConnectOptions::new(format!("sqlite://{}?mode=rwc", path.display()));
+        opt.sqlx_logging(false);
+        let db = Database::connect(opt).await.unwrap();
+        Migrator::up(&db, None).await.unwrap();
+
+        let now = Utc::now().naive_utc();
+        let logs = [
+            (IssueStatus::Unresolved, None),
+            (
+                IssueStatus::MutedUntilTime,
+                Some(now - Duration::minutes(1)),
+            ),
+            (
+                IssueStatus::MutedUntilTime,
+                Some(now + Duration::minutes(1)),
+            ),
+            (IssueStatus::Ignored, None),
+        ];
+        for (index, (status, muted_until)) in logs.into_iter().enumerate() {
+            upload_log::ActiveModel {
+                hash: Set(format!("hash{}", index)),
+                user_list: Set(String::new()),
+                first_time: Set(now),
+                last_time: Set(now),
+                total_count: Set(1),
+                status: Set(status),
+                resolution_time: Set(now),
+                log_type: Set("error".into()),
+                message: Set(String::new()),
+                project_id: Set(0),
+                muted_until: Set(muted_until),
+                ..Default::default()
+            }
+            .insert(&db)
+            .await
+            .unwrap();
+        }
+
+        let unresolved = UploadLog::find()
+            .filter(unresolved_condition(now))
+            .order_by_asc(upload_log::Column::Id)
+            .all(&db)
+            .await
+            .unwrap();
+        assert_eq!(
+            unresolved
+                .iter()
+                .map(|x| x.hash.as_str())
+                .collect::<Vec<_>>(),
+            ["hash0", "hash1"]
+        );
+        assert!(unresolved
+            .iter()
+            .all(|x| x.status.effective(x.muted_until, now) == IssueStatus::Unresolved));
+
+        // 静音到期之后再查询, 两个静音的错误都视为未解决
+        let later = now + Duration::minutes(2);
+        let count = UploadLog::find()
+            .filter(unresolved_condition(later))
+            .count(&db)
+            .await
+            .unwrap();
+        assert_eq!(count, 3);
+
+        drop(db);
+        let _ = std::fs::remove_file(path);
+    }
+}
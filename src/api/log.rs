@@ -1,49 +1,63 @@
-use crate::api::{map_db_err, user_authentication, AppState};
-use crate::orm_entities::prelude::{UploadLog, UploadUser};
-use crate::orm_entities::{upload_log, upload_user};
+use crate::api::issue_status::{
+    change_status, find_regression, find_resolved_versions, record_package_versions,
+    record_status_change, reopen_expr, unresolved_condition, StatusChange,
+};
+use crate::api::project::authorize_ingest;
+use crate::api::release;
+use crate::api::version::VersionSchemes;
+use crate::api::{
+    json_error, map_db_err, user_authentication, AppState, ReportTime, SamplingPolicy,
+};
+use crate::orm_entities::prelude::{
+    LogPackageVersion, SampleStratum, UploadLog, UploadOccurrence, UploadUser,
+};
+use crate::orm_entities::sea_orm_active_enums::{IssueStatus, LogAction};
+use crate::orm_entities::{
+    log_package_version, sample_stratum, upload_event, upload_log, upload_occurrence, upload_user,
+};
+use actix_web::http::StatusCode;
 use actix_web::{post, web, HttpRequest, HttpResponse, Responder};
 use actix_web_httpauth::extractors::basic::BasicAuth;
-use chrono::Utc;
-use regex::Regex;
+use chrono::{NaiveDateTime, Utc};
+use sea_orm::sea_query::{Expr, OnConflict, Query, SimpleExpr};
 use sea_orm::ActiveValue::Set;
 use sea_orm::{
-    ActiveModelTrait, ColumnTrait, Condition, EntityTrait, ModelTrait, NotSet, PaginatorTrait,
-    QueryFilter, QueryOrder, QuerySelect,
+    ActiveEnum, ActiveModelTrait, ColumnTrait, Condition, ConnectionTrait, DbErr, EntityTrait,
+    NotSet, PaginatorTrait, QueryFilter, QueryOrder, QuerySelect, QueryTrait, TransactionTrait,
 };
 use serde::{Deserialize, Serialize};
 
 #[derive(Deserialize, Debug)]
-struct UploadLogData {
+pub struct UploadLogData {
     // 日志类型
-    log_type: String,
+    pub log_type: String,
     // 消息
-    message: String,
+    pub message: String,
     // 用户
-    user: String,
+    pub user: String,
     // 包名
-    package: String,
+    pub package: String,
     // 导航服
-    nav_url: String,
+    pub nav_url: String,
     // 版本信息
-    version: String,
+    pub version: String,
     // logs
     #[serde(default = "default_string")]
-    logs: String,
+    pub logs: String,
+    // 指定分组指纹, 为空时根据调用栈或消息内容计算
+    #[serde(default)]
+    pub fingerprint: Option<String>,
+    // 客户端产生日志的时间(unix 时间戳, 秒), 为空或时钟异常时使用服务器接收时间
+    #[serde(default)]
+    pub time: Option<i64>,
 }
 
 fn default_string() -> String {
     "".into()
 }
 
-fn normalize_error_message(error_msg: &str) -> String {
-    // 替换内存地址（0x开头，后面跟着十六进制数字）
-    let addr_regex = Regex::new(r"0x[0-9a-fA-F]+").unwrap();
-    let normalized = addr_regex
-        .replace_all(error_msg, "[MEMORY_ADDRESS]")
-        .to_string();
-
-    normalized
-}
+// 批量上报单次最多条数
+const MAX_BATCH_SIZE: usize = 500;
 
 #[post("/api/upload_log")]
 pub async fn api_upload_log(
@@ -54,101 +68,599 @@ pub async fn api_upload_log(
     // println!("{:?}", req);
     // println!("{:?}", json_data);
 
-    if json_data.log_type.starts_with("error") {
-        // 计算消息内容哈希值
-        let normalized = normalize_error_message(&json_data.message);
-        let digest = md5::compute(format!("{}-{}", normalized, json_data.log_type));
-        let hash_string = format!("{:x}", digest);
+    app_data
+        .rate_limiter
+        .check(&req, [json_data.package.as_str()], 1)?;
+    let project_id = authorize_ingest(&req, &app_data).await?;
 
-        let log_data = UploadLog::find()
-            .filter(upload_log::Column::Hash.eq(&hash_string))
-            .one(app_data.db_pool.get().unwrap())
+    submit_log(&app_data, project_id, &client_ip(&req), json_data.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().body("{\"data\": \"ok\"}"))
+}
+
+/// 提交一条上报, 开启写入队列时放入队列后立即返回, 否则同步写入
+pub async fn submit_log(
+    app_data: &AppState,
+    project_id: i32,
+    ip: &str,
+    json_data: UploadLogData,
+) -> actix_web::Result<()> {
+    let (_, pending) = pending_log(app_data, project_id, ip, json_data);
+    if let Some(pending) = pending {
+        match &app_data.ingest_queue {
+            Some(queue) => queue.push(pending)?,
+            None => record_logs(
+                app_data.db_pool.get().unwrap(),
+                app_data.sampling,
+                &app_data.version_schemes,
+                std::slice::from_ref(&pending),
+            )
             .await
-            .map_err(map_db_err)?;
+            .map_err(map_db_err)?,
+        }
+    }
+    Ok(())
+}
+
+#[derive(Serialize, Debug)]
+struct BatchUploadItemResult {
+    index: usize,
+    success: bool,
+    // 处理方式: issue / event / drop
+    #[serde(skip_serializing_if = "Option::is_none")]
+    action: Option<LogAction>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
 
-        let mut save_user = true;
-        if let Some(ref log_data) = log_data {
-            if log_data.user_list.split(",").count() > 100 {
-                save_user = false;
+#[derive(Serialize, Debug)]
+struct BatchUploadResponseData {
+    accepted: usize,
+    failed: usize,
+    items: Vec<BatchUploadItemResult>,
+}
+
+/// 批量上报, 用于客户端离线后一次性提交缓存的日志
+///
+/// 所有日志在同一个事务中处理, 单条日志格式错误或写入失败只影响该条, 结果按顺序逐条返回
+#[post("/api/upload_log/batch")]
+pub async fn api_upload_log_batch(
+    req: HttpRequest,
+    app_data: web::Data<AppState>,
+    json_data: web::Json<Vec<serde_json::Value>>,
+) -> actix_web::Result<HttpResponse> {
+    let items = json_data.into_inner();
+    if items.len() > MAX_BATCH_SIZE {
+        return Err(json_error(
+            StatusCode::BAD_REQUEST,
+            &format!("too many logs, at most {} per batch", MAX_BATCH_SIZE),
+        ));
+    }
+
+    // 每条日志消耗一个令牌, 超出限流的日志不写入
+    let allowed = app_data.rate_limiter.check(
+        &req,
+        items
+            .iter()
+            .filter_map(|x| x.get("package").and_then(|x| x.as_str())),
+        items.len().max(1),
+    )?;
+    let project_id = authorize_ingest(&req, &app_data).await?;
+
+    let ip = client_ip(&req);
+    let txn = app_data
+        .db_pool
+        .get()
+        .unwrap()
+        .begin()
+        .await
+        .map_err(map_db_err)?;
+
+    let mut results = Vec::with_capacity(items.len());
+    for (index, item) in items.into_iter().enumerate() {
+        if index >= allowed {
+            results.push(BatchUploadItemResult {
+                index,
+                success: false,
+                action: None,
+                error: Some("too many requests".into()),
+            });
+            continue;
+        }
+
+        let json_data = match serde_json::from_value::<UploadLogData>(item) {
+            Ok(x) => x,
+            Err(err) => {
+                results.push(BatchUploadItemResult {
+                    index,
+                    success: false,
+                    action: None,
+                    error: Some(err.to_string()),
+                });
+                continue;
+            }
+        };
+
+        // 每条日志使用独立的保存点, 失败时只回滚该条
+        let savepoint = txn.begin().await.map_err(map_db_err)?;
+        match ingest_log(&savepoint, &app_data, project_id, &ip, json_data).await {
+            Ok(action) => {
+                savepoint.commit().await.map_err(map_db_err)?;
+                results.push(BatchUploadItemResult {
+                    index,
+                    success: true,
+                    action: Some(action),
+                    error: None,
+                });
+            }
+            Err(err) => {
+                savepoint.rollback().await.map_err(map_db_err)?;
+                results.push(BatchUploadItemResult {
+                    index,
+                    success: false,
+                    action: None,
+                    error: Some(err.to_string()),
+                });
             }
         }
+    }
 
-        let mut user_id = None;
-        if save_user {
-            let ip = if let Some(x) = req.connection_info().realip_remote_addr() {
-                x.to_string()
-            } else {
-                "unknown".to_string()
-            };
-            let user = upload_user::ActiveModel {
-                id: NotSet,
-                package: Set(json_data.package.to_owned()),
-                nav_url: Set(json_data.nav_url.to_owned()),
-                version: Set(json_data.version.to_owned()),
-                logs: Set(json_data.logs.to_owned()),
-                user: Set(json_data.user.to_owned()),
-                ip: Set(ip),
-                time: Set(Utc::now().naive_utc()),
-            };
-
-            let user = user
-                .save(app_data.db_pool.get().unwrap())
-                .await
-                .map_err(map_db_err)?;
-            user_id = Some(user.id.unwrap());
+    txn.commit().await.map_err(map_db_err)?;
+
+    let accepted = results.iter().filter(|x| x.success).count();
+    Ok(HttpResponse::Ok().json(BatchUploadResponseData {
+        accepted,
+        failed: results.len() - accepted,
+        items: results,
+    }))
+}
+
+pub fn client_ip(req: &HttpRequest) -> String {
+    if let Some(x) = req.connection_info().realip_remote_addr() {
+        x.to_string()
+    } else {
+        "unknown".to_string()
+    }
+}
+
+/// 一条待写入数据库的上报
+pub struct PendingLog {
+    pub project_id: i32,
+    pub ip: String,
+    pub data: UploadLogData,
+    pub time: ReportTime,
+    /// 错误的分组哈希值, 为空时作为原始事件保存
+    pub hash: Option<String>,
+}
+
+/// 按 log_type 对应的处理方式生成待写入的上报, 丢弃时返回 None
+fn pending_log(
+    app_data: &AppState,
+    project_id: i32,
+    ip: &str,
+    mut json_data: UploadLogData,
+) -> (LogAction, Option<PendingLog>) {
+    let action = app_data
+        .log_type_rules
+        .action(project_id, &json_data.log_type);
+    let hash = match action {
+        LogAction::Issue => Some(
+            app_data
+                .fingerprinter
+                .fingerprint(
+                    project_id,
+                    &json_data.log_type,
+                    &json_data.message,
+                    json_data.fingerprint.as_deref(),
+                )
+                .hash,
+        ),
+        LogAction::Event => None,
+        LogAction::Drop => return (action, None),
+    };
+
+    // 项目上报突增时只计数, 不保存日志内容
+    if app_data.spike_detector.observe(project_id) {
+        json_data.logs.clear();
+    }
+
+    let time = app_data
+        .clock
+        .resolve(json_data.time, Utc::now().naive_utc());
+    (
+        action,
+        Some(PendingLog {
+            project_id,
+            ip: ip.to_string(),
+            data: json_data,
+            time,
+            hash,
+        }),
+    )
+}
+
+/// 按 log_type 对应的处理方式记录一条上报
+async fn ingest_log<C: ConnectionTrait>(
+    db: &C,
+    app_data: &AppState,
+    project_id: i32,
+    ip: &str,
+    json_data: UploadLogData,
+) -> Result<LogAction, DbErr> {
+    let (action, pending) = pending_log(app_data, project_id, ip, json_data);
+    if let Some(pending) = pending {
+        record_logs(
+            db,
+            app_data.sampling,
+            &app_data.version_schemes,
+            std::slice::from_ref(&pending),
+        )
+        .await?;
+    }
+    Ok(action)
+}
+
+/// 写入一批上报, 同一哈希值的错误合并为一次更新
+pub async fn record_logs<C: ConnectionTrait>(
+    db: &C,
+    sampling: SamplingPolicy,
+    versions: &VersionSchemes,
+    logs: &[PendingLog],
+) -> Result<(), DbErr> {
+    let mut issues: Vec<(&str, Vec<&PendingLog>)> = vec![];
+    for log in logs {
+        match &log.hash {
+            Some(hash) => match issues.iter_mut().find(|(x, _)| x == hash) {
+                Some((_, group)) => group.push(log),
+                None => issues.push((hash, vec![log])),
+            },
+            None => record_event(db, log).await?,
         }
+    }
+    for (hash, group) in issues {
+        record_issue(db, sampling, versions, hash, &group).await?;
+    }
+    Ok(())
+}
 
-        let log_active_model = if let Some(log_data) = log_data {
-            // 上报总数
-            let total_count = log_data.total_count + 1;
-            // 上报用户列表
-            let mut user_list: Vec<_> = log_data
-                .user_list
-                .split(",")
-                .map(|x| x.to_string())
-                .collect();
-            if let Some(user_id) = user_id {
-                user_list.push(format!("{}", user_id));
-            }
-            let user_list = user_list.join(",");
-            // 状态更新
-            let status = if log_data.status == 0 { 0 } else { -1 };
+/// 按分组指纹记录为错误, `logs` 为同一哈希值的上报
+async fn record_issue<C: ConnectionTrait>(
+    db: &C,
+    sampling: SamplingPolicy,
+    versions: &VersionSchemes,
+    hash_string: &str,
+    logs: &[&PendingLog],
+) -> Result<(), DbErr> {
+    let Some(first) = logs.first() else {
+        return Ok(());
+    };
+    let count = logs.len() as i32;
+    // 离线缓存的日志可能早于已记录的时间
+    let min_time = logs.iter().map(|x| x.time.time).min().unwrap();
+    let max_time = logs.iter().map(|x| x.time.time).max().unwrap();
 
-            let mut log_active_model: upload_log::ActiveModel = log_data.into();
-            log_active_model.total_count = Set(total_count);
-            log_active_model.last_time = Set(Utc::now().naive_utc());
-            log_active_model.user_list = Set(user_list);
-            log_active_model.status = Set(status);
+    // 首次上报时先插入计数为 0 的错误, 之后与已存在的错误一样累加计数,
+    // 并发上报同一错误时只会插入一条, 计数也不会相互覆盖
+    let (log_data, new_issue) = match find_log_by_hash(db, hash_string).await? {
+        Some(log_data) => (log_data, false),
+        None => {
+            let inserted = insert_issue_if_absent(db, hash_string, first, min_time).await?;
+            let log_data = find_log_by_hash(db, hash_string)
+                .await?
+                .ok_or(DbErr::RecordNotFound(format!("upload_log {}", hash_string)))?;
+            (log_data, inserted)
+        }
+    };
+
+    // 已合并的错误累计自身的上报次数, 上报记录到主错误
+    let primary = match log_data.merged_into {
+        Some(primary_id) => {
+            increase_issue_count(db, log_data.id, count, min_time, max_time, None).await?;
+            UploadLog::find_by_id(primary_id)
+                .one(db)
+                .await?
+                .ok_or(DbErr::RecordNotFound(format!("upload_log {}", primary_id)))?
+        }
+        None => log_data,
+    };
+    let (log_id, previous_status) = (primary.id, primary.status);
+    let resolved_versions = if primary.status.is_resolved() {
+        find_resolved_versions(db, log_id).await?
+    } else {
+        Default::default()
+    };
+    let reports = || {
+        logs.iter()
+            .map(|x| (x.data.package.as_str(), x.data.version.as_str()))
+    };
+    let regression = find_regression(&primary, versions, &resolved_versions, reports());
+    let status = reopen_expr(count, regression.is_some(), primary.resolution_time);
+    increase_issue_count(db, log_id, count, min_time, max_time, Some(status)).await?;
+    record_package_versions(db, versions, log_id, reports()).await?;
 
-            log_active_model
+    let log_data = UploadLog::find_by_id(log_id)
+        .one(db)
+        .await?
+        .ok_or(DbErr::RecordNotFound(format!("upload_log {}", log_id)))?;
+    if log_data.status != previous_status {
+        let reason = if log_data.status == IssueStatus::Reopened {
+            "new occurrence after resolution"
         } else {
-            let mut user_list: Vec<String> = vec![];
-            if let Some(user_id) = user_id {
-                user_list.push(format!("{user_id}"));
-            }
-            let user_list = user_list.join(",");
-
-            upload_log::ActiveModel {
-                id: NotSet,
-                hash: Set(hash_string),
-                user_list: Set(user_list),
-                first_time: Set(Utc::now().naive_utc()),
-                last_time: Set(Utc::now().naive_utc()),
-                total_count: Set(1),
-                status: Set(0),
-                resolution_time: Set(Utc::now().naive_utc()),
-                log_type: Set(json_data.log_type.to_owned()),
-                message: Set(json_data.message.to_owned()),
-            }
+            "mute expired"
         };
+        record_status_change(db, log_id, previous_status, log_data.status, reason, "").await?;
+    }
+    // 判断之后解决版本被修改时不计为复现
+    let regressed = regression.filter(|_| log_data.status == IssueStatus::Reopened);
+    release::record_issue(db, logs, new_issue, regressed).await?;
 
-        log_active_model
-            .save(app_data.db_pool.get().unwrap())
-            .await
-            .map_err(map_db_err)?;
+    // 按采样策略决定是否保存每次上报的详情
+    let mut stored = UploadOccurrence::find()
+        .filter(upload_occurrence::Column::LogId.eq(log_data.id))
+        .filter(upload_occurrence::Column::Sampled.eq(false))
+        .count(db)
+        .await?;
+
+    let base_count = (log_data.total_count - count) as u64;
+    for (index, log) in logs.iter().enumerate() {
+        if stored < sampling.keep_first {
+            insert_occurrence(db, log_data.id, hash_string, log, false).await?;
+            stored += 1;
+        } else if sampling.reservoir > 0 {
+            let seen = increase_stratum_seen(db, log_data.id, &log.data).await?;
+            if let Some(slot) = sampling.reservoir_slot(seen) {
+                replace_sample(db, log_data.id, hash_string, log, slot).await?;
+            }
+        } else if sampling.should_keep(stored, base_count + index as u64 + 1) {
+            insert_occurrence(db, log_data.id, hash_string, log, false).await?;
+            stored += 1;
+        }
     }
+    Ok(())
+}
 
-    Ok(HttpResponse::Ok().body("{\"data\": \"ok\"}"))
+/// 保存一条上报详情
+async fn insert_occurrence<C: ConnectionTrait>(
+    db: &C,
+    log_id: i32,
+    hash: &str,
+    log: &PendingLog,
+    sampled: bool,
+) -> Result<(), DbErr> {
+    let user = upload_user::ActiveModel {
+        id: NotSet,
+        package: Set(log.data.package.to_owned()),
+        nav_url: Set(log.data.nav_url.to_owned()),
+        version: Set(log.data.version.to_owned()),
+        logs: Set(log.data.logs.to_owned()),
+        user: Set(log.data.user.to_owned()),
+        ip: Set(log.ip.to_owned()),
+        time: Set(log.time.time),
+        receive_time: Set(log.time.receive_time),
+        clock_skew: Set(log.time.clock_skew),
+    }
+    .insert(db)
+    .await?;
+
+    upload_occurrence::ActiveModel {
+        id: NotSet,
+        log_id: Set(log_id),
+        user_id: Set(user.id),
+        time: Set(log.time.time),
+        hash: Set(hash.to_string()),
+        sampled: Set(sampled),
+    }
+    .insert(db)
+    .await?;
+    Ok(())
+}
+
+/// 累加错误在该包名及版本下的上报次数, 返回包含本次在内的次数
+async fn increase_stratum_seen<C: ConnectionTrait>(
+    db: &C,
+    log_id: i32,
+    data: &UploadLogData,
+) -> Result<u64, DbErr> {
+    SampleStratum::insert(sample_stratum::ActiveModel {
+        id: NotSet,
+        log_id: Set(log_id),
+        package: Set(data.package.to_owned()),
+        version: Set(data.version.to_owned()),
+        seen: Set(1),
+    })
+    .on_conflict(
+        OnConflict::columns([
+            sample_stratum::Column::LogId,
+            sample_stratum::Column::Package,
+            sample_stratum::Column::Version,
+        ])
+        .value(
+            sample_stratum::Column::Seen,
+            Expr::col(sample_stratum::Column::Seen).add(1),
+        )
+        .to_owned(),
+    )
+    .exec_without_returning(db)
+    .await?;
+
+    let stratum = SampleStratum::find()
+        .filter(sample_stratum::Column::LogId.eq(log_id))
+        .filter(sample_stratum::Column::Package.eq(&data.package))
+        .filter(sample_stratum::Column::Version.eq(&data.version))
+        .one(db)
+        .await?
+        .ok_or(DbErr::RecordNotFound("sample_stratum".into()))?;
+    Ok(stratum.seen.max(0) as u64)
+}
+
+/// 将上报详情保存到蓄水池的 `slot` 位置, 该位置已有上报详情时替换
+async fn replace_sample<C: ConnectionTrait>(
+    db: &C,
+    log_id: i32,
+    hash: &str,
+    log: &PendingLog,
+    slot: u64,
+) -> Result<(), DbErr> {
+    let replaced = UploadOccurrence::find()
+        .inner_join(UploadUser)
+        .filter(upload_occurrence::Column::LogId.eq(log_id))
+        .filter(upload_occurrence::Column::Sampled.eq(true))
+        .filter(upload_user::Column::Package.eq(&log.data.package))
+        .filter(upload_user::Column::Version.eq(&log.data.version))
+        .order_by_asc(upload_occurrence::Column::Id)
+        .offset(slot)
+        .one(db)
+        .await?;
+    if let Some(replaced) = replaced {
+        UploadOccurrence::delete_by_id(replaced.id).exec(db).await?;
+        UploadUser::delete_by_id(replaced.user_id).exec(db).await?;
+    }
+
+    insert_occurrence(db, log_id, hash, log, true).await
+}
+
+/// 不存在该哈希值的错误时插入一条计数为 0 的错误, 返回是否插入
+async fn insert_issue_if_absent<C: ConnectionTrait>(
+    db: &C,
+    hash: &str,
+    log: &PendingLog,
+    time: NaiveDateTime,
+) -> Result<bool, DbErr> {
+    let fingerprint = log
+        .data
+        .fingerprint
+        .as_ref()
+        .map(|x| x.trim().to_string())
+        .filter(|x| !x.is_empty());
+
+    let query = Query::insert()
+        .into_table(upload_log::Entity)
+        .columns([
+            upload_log::Column::Hash,
+            upload_log::Column::UserList,
+            upload_log::Column::FirstTime,
+            upload_log::Column::LastTime,
+            upload_log::Column::TotalCount,
+            upload_log::Column::Status,
+            upload_log::Column::ResolutionTime,
+            upload_log::Column::LogType,
+            upload_log::Column::Message,
+            upload_log::Column::ProjectId,
+            upload_log::Column::Fingerprint,
+        ])
+        .select_from(
+            Query::select()
+                .exprs([
+                    Expr::val(hash),
+                    Expr::val(""),
+                    Expr::val(time),
+                    Expr::val(time),
+                    Expr::val(0),
+                    Expr::val(IssueStatus::Unresolved.to_value()),
+                    Expr::val(time),
+                    Expr::val(log.data.log_type.as_str()),
+                    Expr::val(log.data.message.as_str()),
+                    Expr::val(log.project_id),
+                    Expr::val(fingerprint),
+                ])
+                .and_where(
+                    Expr::exists(
+                        Query::select()
+                            .expr(Expr::val(1))
+                            .from(upload_log::Entity)
+                            .and_where(upload_log::Column::Hash.eq(hash))
+                            .to_owned(),
+                    )
+                    .not(),
+                )
+                .to_owned(),
+        )
+        .map_err(|err| DbErr::Custom(err.to_string()))?
+        .to_owned();
+
+    let result = db.execute(db.get_database_backend().build(&query)).await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// 在数据库中累加错误的上报次数及更新上报时间, `status` 不为空时同时更新错误状态
+async fn increase_issue_count<C: ConnectionTrait>(
+    db: &C,
+    id: i32,
+    count: i32,
+    min_time: NaiveDateTime,
+    max_time: NaiveDateTime,
+    status: Option<SimpleExpr>,
+) -> Result<(), DbErr> {
+    let mut query = UploadLog::update_many()
+        .col_expr(
+            upload_log::Column::TotalCount,
+            Expr::col(upload_log::Column::TotalCount).add(count),
+        )
+        .col_expr(
+            upload_log::Column::FirstTime,
+            Expr::cust_with_values("MIN(first_time, ?)", [min_time]),
+        )
+        .col_expr(
+            upload_log::Column::LastTime,
+            Expr::cust_with_values("MAX(last_time, ?)", [max_time]),
+        );
+    if let Some(status) = status {
+        query = query.col_expr(upload_log::Column::Status, status);
+    }
+    query
+        .filter(upload_log::Column::Id.eq(id))
+        .exec(db)
+        .await?;
+    Ok(())
+}
+
+/// 只保存原始事件, 不参与错误分组
+async fn record_event<C: ConnectionTrait>(db: &C, log: &PendingLog) -> Result<(), DbErr> {
+    upload_event::ActiveModel {
+        id: NotSet,
+        project_id: Set(log.project_id),
+        log_type: Set(log.data.log_type.to_owned()),
+        message: Set(log.data.message.to_owned()),
+        user: Set(log.data.user.to_owned()),
+        package: Set(log.data.package.to_owned()),
+        nav_url: Set(log.data.nav_url.to_owned()),
+        version: Set(log.data.version.to_owned()),
+        logs: Set(log.data.logs.to_owned()),
+        ip: Set(log.ip.to_owned()),
+        time: Set(log.time.time),
+        receive_time: Set(log.time.receive_time),
+        clock_skew: Set(log.time.clock_skew),
+    }
+    .insert(db)
+    .await?;
+
+    release::record_event(db, log).await
+}
+
+/// 按哈希值查找错误, 同一哈希值同时存在未合并及已合并的错误时优先返回未合并的
+pub async fn find_log_by_hash<C: ConnectionTrait>(
+    db: &C,
+    hash: &str,
+) -> Result<Option<upload_log::Model>, DbErr> {
+    UploadLog::find()
+        .filter(upload_log::Column::Hash.eq(hash))
+        .order_by_asc(upload_log::Column::MergedInto)
+        .one(db)
+        .await
+}
+
+/// 按哈希值查找错误, 已合并的错误返回其主错误
+pub async fn find_issue_by_hash<C: ConnectionTrait>(
+    db: &C,
+    hash: &str,
+) -> Result<Option<upload_log::Model>, DbErr> {
+    match find_log_by_hash(db, hash).await? {
+        Some(log) => match log.merged_into {
+            Some(primary_id) => UploadLog::find_by_id(primary_id).one(db).await,
+            None => Ok(Some(log)),
+        },
+        None => Ok(None),
+    }
 }
 
 #[derive(Deserialize, Debug)]
@@ -156,6 +668,9 @@ struct LogListRequestData {
     page: i32,
     page_size: i32,
     log_type: String,
+    // 项目id, 为空时不过滤
+    #[serde(default)]
+    project_id: Option<i32>,
 }
 
 #[derive(Serialize, Debug)]
@@ -164,7 +679,7 @@ struct LogListItemData {
     first_time: i64,
     last_time: i64,
     total_count: i32,
-    status: i32,
+    status: IssueStatus,
     message: String,
 }
 
@@ -200,16 +715,27 @@ pub async fn api_log_list(
         ))?;
 
     // 用户鉴权
-    let is_admin = user_authentication(&req, &credentials, &app_data).await?;
+    let user = user_authentication(&req, &credentials, &app_data).await?;
 
-    // 构建查询条件
-    let mut condition = Condition::all();
+    // 构建查询条件, 已合并的错误不单独显示
+    let mut condition = Condition::all().add(upload_log::Column::MergedInto.is_null());
 
     // 如果 log_type 不为空，添加类型过滤
     if !json_data.log_type.is_empty() {
         condition = condition.add(upload_log::Column::LogType.eq(json_data.log_type.clone()));
     }
 
+    if let Some(project_id) = json_data.project_id {
+        condition = condition.add(upload_log::Column::ProjectId.eq(project_id));
+    }
+
+    // 只能查看有权限的项目
+    if let Some(projects) = &user.projects {
+        let mut projects = projects.clone();
+        projects.push(0);
+        condition = condition.add(upload_log::Column::ProjectId.is_in(projects));
+    }
+
     // 计算分页
     let page = json_data.page.max(1);
     let page_size = json_data.page_size.clamp(1, 100);
@@ -226,9 +752,10 @@ pub async fn api_log_list(
         as i32;
 
     // 查询未解决数量
+    let now = Utc::now().naive_utc();
     let pending_condition = Condition::all()
         .add(condition.clone())
-        .add(upload_log::Column::Status.eq(0));
+        .add(unresolved_condition(now));
 
     let pending_count = UploadLog::find()
         .filter(pending_condition)
@@ -238,10 +765,11 @@ pub async fn api_log_list(
         .map_err(|e| actix_web::error::ErrorInternalServerError(format!("Database error: {}", e)))?
         as i32;
 
-    // 查询已解决数量（status = 1）
-    let solved_condition = Condition::all()
-        .add(condition.clone())
-        .add(upload_log::Column::Status.eq(1));
+    // 查询已解决数量
+    let solved_condition = Condition::all().add(condition.clone()).add(
+        upload_log::Column::Status
+            .is_in([IssueStatus::Resolved, IssueStatus::ResolvedInNextRelease]),
+    );
 
     let solved_count = UploadLog::find()
         .filter(solved_condition)
@@ -278,7 +806,7 @@ pub async fn api_log_list(
             first_time: log.first_time.and_utc().timestamp(),
             last_time: log.last_time.and_utc().timestamp(),
             total_count: log.total_count,
-            status: log.status,
+            status: log.status.effective(log.muted_until, now),
             message: log.message,
         })
         .collect();
@@ -290,7 +818,7 @@ pub async fn api_log_list(
         pending: pending_count,
         solved: solved_count,
         total_pages,
-        is_admin,
+        is_admin: user.is_admin(),
         items,
     };
 
@@ -311,6 +839,15 @@ struct LogContentResponseBriefUserData {
     user: String,
     ip: String,
     time: String,
+    receive_time: String,
+    // 客户端时间减去接收时间的秒数
+    clock_skew: Option<i64>,
+}
+
+#[derive(Serialize, Debug)]
+struct LogContentResolvedVersionData {
+    package: String,
+    version: String,
 }
 
 #[derive(Serialize, Debug)]
@@ -320,11 +857,18 @@ struct LogContentResponseData {
     first_time: i64,
     last_time: i64,
     total_count: i32,
-    ///  0 未解决， 1 已解决, -1已解决之后又上报了
-    status: i32,
+    status: IssueStatus,
     resolution_time: i64,
+    // 静音截止时间
+    muted_until: Option<i64>,
+    // 静音截止的累计上报次数
+    muted_until_count: Option<i32>,
+    // 各包名解决的版本
+    resolved_versions: Vec<LogContentResolvedVersionData>,
     message: String,
     can_remove: bool,
+    // 合并到该错误的哈希值
+    merged_hashes: Vec<String>,
 }
 
 #[post("/api/log_content")]
@@ -334,35 +878,60 @@ pub async fn api_log_content(
     app_data: web::Data<AppState>,
     json_data: web::Json<LogContentRequestData>,
 ) -> actix_web::Result<HttpResponse> {
-    let is_admin = user_authentication(&req, &credentials, &app_data).await?;
+    let user = user_authentication(&req, &credentials, &app_data).await?;
 
-    let logs = UploadLog::find()
-        .filter(upload_log::Column::Hash.eq(&json_data.hash))
-        .one(app_data.db_pool.get().unwrap())
+    let logs = find_issue_by_hash(app_data.db_pool.get().unwrap(), &json_data.hash)
         .await
-        .map_err(map_db_err)?;
+        .map_err(map_db_err)?
+        .filter(|x| user.can_view_project(x.project_id));
 
     if let Some(logs) = logs {
-        let mut user_list = vec![];
-
-        for id in logs.user_list.split(",") {
-            if let Some(user_data) = UploadUser::find()
-                .filter(upload_user::Column::Id.eq(id))
-                .one(app_data.db_pool.get().unwrap())
-                .await
-                .map_err(map_db_err)?
-            {
-                user_list.push(LogContentResponseBriefUserData {
-                    id: user_data.id,
-                    package: user_data.package,
-                    nav_url: user_data.nav_url,
-                    version: user_data.version,
-                    user: user_data.user,
-                    ip: user_data.ip,
-                    time: user_data.time.format("%m-%d %H:%M:%S").to_string(),
-                });
-            }
-        }
+        let merged_hashes = UploadLog::find()
+            .filter(upload_log::Column::MergedInto.eq(logs.id))
+            .order_by_asc(upload_log::Column::Id)
+            .all(app_data.db_pool.get().unwrap())
+            .await
+            .map_err(map_db_err)?
+            .into_iter()
+            .map(|x| x.hash)
+            .collect();
+
+        let user_list = UploadUser::find()
+            .inner_join(UploadOccurrence)
+            .filter(upload_occurrence::Column::LogId.eq(logs.id))
+            .order_by_asc(upload_occurrence::Column::Id)
+            .all(app_data.db_pool.get().unwrap())
+            .await
+            .map_err(map_db_err)?
+            .into_iter()
+            .map(|user_data| LogContentResponseBriefUserData {
+                id: user_data.id,
+                package: user_data.package,
+                nav_url: user_data.nav_url,
+                version: user_data.version,
+                user: user_data.user,
+                ip: user_data.ip,
+                time: user_data.time.format("%m-%d %H:%M:%S").to_string(),
+                receive_time: user_data.receive_time.format("%m-%d %H:%M:%S").to_string(),
+                clock_skew: user_data.clock_skew,
+            })
+            .collect();
+
+        let resolved_versions = LogPackageVersion::find()
+            .filter(log_package_version::Column::LogId.eq(logs.id))
+            .filter(log_package_version::Column::ResolvedVersion.is_not_null())
+            .order_by_asc(log_package_version::Column::Package)
+            .all(app_data.db_pool.get().unwrap())
+            .await
+            .map_err(map_db_err)?
+            .into_iter()
+            .filter_map(|x| {
+                Some(LogContentResolvedVersionData {
+                    package: x.package,
+                    version: x.resolved_version?,
+                })
+            })
+            .collect();
 
         let response = LogContentResponseData {
             hash: logs.hash,
@@ -370,10 +939,20 @@ pub async fn api_log_content(
             first_time: logs.first_time.and_utc().timestamp(),
             last_time: logs.last_time.and_utc().timestamp(),
             total_count: logs.total_count,
-            status: logs.status,
+            status: logs
+                .status
+                .effective(logs.muted_until, Utc::now().naive_utc()),
             resolution_time: logs.resolution_time.and_utc().timestamp(),
+            muted_until: logs.muted_until.map(|x| x.and_utc().timestamp()),
+            muted_until_count: logs.muted_until_count,
+            resolved_versions,
             message: logs.message,
-            can_remove: if logs.status == 1 { is_admin } else { false },
+            can_remove: if logs.status.is_resolved() {
+                user.is_admin()
+            } else {
+                false
+            },
+            merged_hashes,
         };
 
         Ok(HttpResponse::Ok().body(serde_json::to_string(&response)?))
@@ -382,28 +961,53 @@ pub async fn api_log_content(
     }
 }
 
+#[derive(Deserialize, Debug)]
+struct LogCompleteRequestData {
+    hash: String,
+    // 解决的版本, 该版本及之后版本的上报才算复现; 为空时任何上报都算复现
+    #[serde(default)]
+    version: Option<String>,
+    // 解决版本对应的包名, 为空时用于该错误上报过的所有包名
+    #[serde(default)]
+    package: Option<String>,
+    #[serde(default)]
+    reason: String,
+}
+
 #[post("/api/log_complete")]
 pub async fn api_log_complete(
     req: HttpRequest,
     credentials: BasicAuth,
     app_data: web::Data<AppState>,
-    json_data: web::Json<LogContentRequestData>,
+    json_data: web::Json<LogCompleteRequestData>,
 ) -> actix_web::Result<impl Responder> {
-    let _ = user_authentication(&req, &credentials, &app_data).await?;
+    let user = user_authentication(&req, &credentials, &app_data).await?;
+    if !user.can_write() {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
 
-    if let Some(log_data_model) = UploadLog::find()
-        .filter(upload_log::Column::Hash.eq(&json_data.hash))
-        .one(app_data.db_pool.get().unwrap())
+    let db = app_data.db_pool.get().unwrap();
+    if let Some(log_data_model) = find_issue_by_hash(db, &json_data.hash)
         .await
         .map_err(map_db_err)?
+        .filter(|x| user.can_view_project(x.project_id))
     {
-        let mut log_active_model: upload_log::ActiveModel = log_data_model.into();
-        log_active_model.status = Set(1);
-        log_active_model.resolution_time = Set(Utc::now().naive_utc());
-        log_active_model
-            .save(app_data.db_pool.get().unwrap())
-            .await
-            .map_err(map_db_err)?;
+        let change = StatusChange {
+            status: IssueStatus::Resolved,
+            muted_until: None,
+            mute_count: None,
+            version: json_data.version.clone(),
+            package: json_data.package.clone(),
+        };
+        change_status(
+            db,
+            &app_data.version_schemes,
+            log_data_model,
+            change,
+            json_data.reason.trim(),
+            &user.username,
+        )
+        .await?;
     }
 
     Ok(HttpResponse::Ok().body("{\"data\": \"ok\"}"))
@@ -416,30 +1020,39 @@ pub async fn api_log_remove(
     app_data: web::Data<AppState>,
     json_data: web::Json<LogContentRequestData>,
 ) -> actix_web::Result<impl Responder> {
-    if !user_authentication(&req, &credentials, &app_data).await? {
+    if !user_authentication(&req, &credentials, &app_data)
+        .await?
+        .is_admin()
+    {
         return Ok(HttpResponse::Forbidden().finish());
     }
 
-    if let Some(log_data_model) = UploadLog::find()
-        .filter(upload_log::Column::Hash.eq(&json_data.hash))
-        .one(app_data.db_pool.get().unwrap())
-        .await
-        .map_err(map_db_err)?
+    if let Some(log_data_model) =
+        find_issue_by_hash(app_data.db_pool.get().unwrap(), &json_data.hash)
+            .await
+            .map_err(map_db_err)?
     {
-        for id in log_data_model.user_list.split(",") {
-            let upload_user = UploadUser::find()
-                .filter(upload_user::Column::Id.eq(id))
-                .one(app_data.db_pool.get().unwrap())
-                .await
-                .map_err(map_db_err)?;
-
-            if let Some(upload_user) = upload_user {
-                upload_user
-                    .delete(app_data.db_pool.get().unwrap())
-                    .await
-                    .map_err(map_db_err)?;
-            }
-        }
+        // 删除该错误关联的上报详情
+        UploadUser::delete_many()
+            .filter(
+                upload_user::Column::Id.in_subquery(
+                    UploadOccurrence::find()
+                        .select_only()
+                        .column(upload_occurrence::Column::UserId)
+                        .filter(upload_occurrence::Column::LogId.eq(log_data_model.id))
+                        .into_query(),
+                ),
+            )
+            .exec(app_data.db_pool.get().unwrap())
+            .await
+            .map_err(map_db_err)?;
+
+        // 同时删除合并到该错误的错误
+        UploadLog::delete_many()
+            .filter(upload_log::Column::MergedInto.eq(log_data_model.id))
+            .exec(app_data.db_pool.get().unwrap())
+            .await
+            .map_err(map_db_err)?;
 
         let log_active_model: upload_log::ActiveModel = log_data_model.into();
         log_active_model
@@ -464,16 +1077,33 @@ struct UserLogResponseData {
 
 #[post("/api/user_log")]
 pub async fn api_user_log(
-    _req: HttpRequest,
+    req: HttpRequest,
+    credentials: BasicAuth,
     app_data: web::Data<AppState>,
     json_data: web::Json<UserLogRequestData>,
 ) -> actix_web::Result<HttpResponse> {
-    if let Some(user_data) = UploadUser::find()
-        .filter(upload_user::Column::Id.eq(json_data.id))
-        .one(app_data.db_pool.get().unwrap())
+    let user = user_authentication(&req, &credentials, &app_data).await?;
+
+    // 按上报详情所属的错误校验项目权限, 无权限时与不存在的处理相同
+    let db = app_data.db_pool.get().unwrap();
+    let visible = UploadLog::find()
+        .inner_join(UploadOccurrence)
+        .filter(upload_occurrence::Column::UserId.eq(json_data.id))
+        .one(db)
         .await
         .map_err(map_db_err)?
-    {
+        .is_some_and(|x| user.can_view_project(x.project_id));
+    let user_data = if visible {
+        UploadUser::find()
+            .filter(upload_user::Column::Id.eq(json_data.id))
+            .one(db)
+            .await
+            .map_err(map_db_err)?
+    } else {
+        None
+    };
+
+    if let Some(user_data) = user_data {
         let response = UserLogResponseData {
             id: user_data.id,
             logs: if user_data.logs.is_empty() {
@@ -504,28 +1134,27 @@ pub async fn api_clear_log(
     app_data: web::Data<AppState>,
     json_data: web::Json<crate::api::log::ClearLogResponseData>,
 ) -> actix_web::Result<HttpResponse> {
-    if !user_authentication(&req, &credentials, &app_data).await? {
+    if !user_authentication(&req, &credentials, &app_data)
+        .await?
+        .is_admin()
+    {
         return Ok(HttpResponse::Forbidden().finish());
     }
-    let logs = UploadLog::find()
-        .filter(upload_log::Column::LogType.eq(&json_data.log_type))
-        .all(app_data.db_pool.get().unwrap())
+    UploadUser::delete_many()
+        .filter(
+            upload_user::Column::Id.in_subquery(
+                UploadOccurrence::find()
+                    .select_only()
+                    .column(upload_occurrence::Column::UserId)
+                    .inner_join(UploadLog)
+                    .filter(upload_log::Column::LogType.eq(&json_data.log_type))
+                    .into_query(),
+            ),
+        )
+        .exec(app_data.db_pool.get().unwrap())
         .await
         .map_err(map_db_err)?;
 
-    for log in logs {
-        for v in log
-            .user_list
-            .split(",")
-            .filter_map(|s| s.parse::<i32>().ok())
-        {
-            let _ = UploadUser::delete_by_id(v)
-                .exec(app_data.db_pool.get().unwrap())
-                .await
-                .map_err(map_db_err)?;
-        }
-    }
-
     UploadLog::delete_many()
         .filter(upload_log::Column::LogType.eq(&json_data.log_type))
         .exec(app_data.db_pool.get().unwrap())
@@ -534,3 +1163,83 @@ pub async fn api_clear_log(
 
     Ok(HttpResponse::Ok().body("{\"data\": \"ok\"}"))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::migration::{Migrator, MigratorTrait};
+    use crate::orm_entities::sea_orm_active_enums::VersionScheme;
+    use sea_orm::{ConnectOptions, Database};
+    use std::sync::Arc;
+
+    fn pending(user: usize) -> PendingLog {
+        let now = Utc::now().naive_utc();
+        PendingLog {
+            project_id: 0,
+            ip: "127.0.0.1".into(),
+            data: UploadLogData {
+                log_type: "error".into(),
+                message: "concurrent error".into(),
+                user: format!("user{}", user),
+                package: "package".into(),
+                nav_url: "nav".into(),
+                version: "1.0".into(),
+                logs: String::new(),
+                fingerprint: None,
+                time: None,
+            },
+            time: ReportTime {
+                time: now,
+                receive_time: now,
+                clock_skew: None,
+            },
+            hash: Some("concurrent-hash".into()),
+        }
+    }
+
+    /// 并发上报同一错误时计数及上报详情不丢失
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_uploads_keep_every_occurrence() {
+        let path = std::env::temp_dir().join(format!(
+            "tiny-http-test-{:016x}.db",
+            rand::random::<u64>()
+        ));
+        let mut opt = ConnectOptions::new(format!("sqlite://{}?mode=rwc", path.display()));
+        opt.max_connections(8).sqlx_logging(false);
+        let db = Database::connect(opt).await.unwrap();
+        Migrator::up(&db, None).await.unwrap();
+
+        let sampling = SamplingPolicy {
+            keep_first: u64::MAX,
+            sample_every: 0,
+            reservoir: 0,
+        };
+        let versions = Arc::new(VersionSchemes::new(VersionScheme::Semver));
+        let tasks: Vec<_> = (0..16)
+            .map(|task| {
+                let db = db.clone();
+                let versions = versions.clone();
+                tokio::spawn(async move {
+                    for index in 0..10 {
+                        let log = pending(task * 10 + index);
+                        record_logs(&db, sampling, &versions, std::slice::from_ref(&log))
+                            .await
+                            .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        let logs = UploadLog::find().all(&db).await.unwrap();
+        let occurrences = UploadOccurrence::find().count(&db).await.unwrap();
+        db.close().await.unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0].total_count, 160);
+        assert_eq!(occurrences, 160);
+    }
+}
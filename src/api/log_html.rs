@@ -40,3 +40,15 @@ pub async fn log_content(
     let html = include_str!("../html/log_content.html");
     Ok(HttpResponse::Ok().content_type("text/html").body(html))
 }
+
+#[get("/events")]
+pub async fn events(
+    req: HttpRequest,
+    credentials: BasicAuth,
+    app_data: web::Data<AppState>,
+) -> actix_web::Result<impl Responder> {
+    let _ = user_authentication(&req, &credentials, &app_data).await?;
+
+    let html = include_str!("../html/events.html");
+    Ok(HttpResponse::Ok().content_type("text/html").body(html))
+}
@@ -0,0 +1,329 @@
+use crate::api::issue_status::record_package_versions;
+use crate::api::log::find_issue_by_hash;
+use crate::api::{json_error, map_db_err, user_authentication, AppState};
+use crate::orm_entities::prelude::{LogPackageVersion, UploadLog, UploadOccurrence};
+use crate::orm_entities::sea_orm_active_enums::IssueStatus;
+use crate::orm_entities::{log_package_version, upload_log, upload_occurrence};
+use actix_web::http::StatusCode;
+use actix_web::{post, web, HttpRequest, HttpResponse};
+use actix_web_httpauth::extractors::basic::BasicAuth;
+use sea_orm::sea_query::Expr;
+use sea_orm::ActiveValue::Set;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, EntityTrait, NotSet, QueryFilter, QueryOrder, TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize, Debug)]
+struct LogMergeRequestData {
+    // 主错误的哈希值
+    hash: String,
+    // 合并到主错误的哈希值
+    merge_hashes: Vec<String>,
+}
+
+/// 将多个错误合并到主错误, 之后这些哈希值的上报都记录到主错误
+#[post("/api/log_merge")]
+pub async fn api_log_merge(
+    req: HttpRequest,
+    credentials: BasicAuth,
+    app_data: web::Data<AppState>,
+    json_data: web::Json<LogMergeRequestData>,
+) -> actix_web::Result<HttpResponse> {
+    let user = user_authentication(&req, &credentials, &app_data).await?;
+    if !user.can_write() {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    // 在事务中读取错误, 计数以增量写入, 不覆盖读取之后到达的上报
+    let db = app_data.db_pool.get().unwrap();
+    let txn = db.begin().await.map_err(map_db_err)?;
+    let Some(primary) = find_issue_by_hash(&txn, &json_data.hash)
+        .await
+        .map_err(map_db_err)?
+        .filter(|x| user.can_view_project(x.project_id))
+    else {
+        return Err(json_error(StatusCode::NOT_FOUND, "log not found"));
+    };
+
+    let mut merged = vec![];
+    for hash in &json_data.merge_hashes {
+        let Some(log) = find_issue_by_hash(&txn, hash)
+            .await
+            .map_err(map_db_err)?
+            .filter(|x| user.can_view_project(x.project_id))
+        else {
+            return Err(json_error(
+                StatusCode::NOT_FOUND,
+                &format!("log not found: {}", hash),
+            ));
+        };
+        if log.project_id != primary.project_id {
+            return Err(json_error(
+                StatusCode::BAD_REQUEST,
+                "cannot merge logs of different projects",
+            ));
+        }
+        if log.id != primary.id && !merged.iter().any(|x: &upload_log::Model| x.id == log.id) {
+            merged.push(log);
+        }
+    }
+
+    if merged.is_empty() {
+        return Ok(HttpResponse::Ok().body("{\"data\": \"ok\"}"));
+    }
+    let merged_ids: Vec<i32> = merged.iter().map(|x| x.id).collect();
+
+    UploadOccurrence::update_many()
+        .col_expr(upload_occurrence::Column::LogId, Expr::value(primary.id))
+        .filter(upload_occurrence::Column::LogId.is_in(merged_ids.clone()))
+        .exec(&txn)
+        .await
+        .map_err(map_db_err)?;
+
+    // 之后的上报记录到主错误, 合并的错误已上报的版本也记录到主错误
+    let merged_versions = LogPackageVersion::find()
+        .filter(log_package_version::Column::LogId.is_in(merged_ids.clone()))
+        .all(&txn)
+        .await
+        .map_err(map_db_err)?;
+    record_package_versions(
+        &txn,
+        &app_data.version_schemes,
+        primary.id,
+        merged_versions
+            .iter()
+            .map(|x| (x.package.as_str(), x.latest_version.as_str())),
+    )
+    .await
+    .map_err(map_db_err)?;
+
+    // 已合并到这些错误的错误改为合并到主错误, 这些错误只保留自身的上报次数
+    for log in &merged {
+        let children_count: i32 = UploadLog::find()
+            .filter(upload_log::Column::MergedInto.eq(log.id))
+            .all(&txn)
+            .await
+            .map_err(map_db_err)?
+            .iter()
+            .map(|x| x.total_count)
+            .sum();
+        if children_count > 0 {
+            UploadLog::update_many()
+                .col_expr(
+                    upload_log::Column::TotalCount,
+                    Expr::cust_with_values("MAX(total_count - ?, 0)", [children_count]),
+                )
+                .filter(upload_log::Column::Id.eq(log.id))
+                .exec(&txn)
+                .await
+                .map_err(map_db_err)?;
+        }
+    }
+    UploadLog::update_many()
+        .col_expr(upload_log::Column::MergedInto, Expr::value(primary.id))
+        .filter(
+            upload_log::Column::Id
+                .is_in(merged_ids.clone())
+                .or(upload_log::Column::MergedInto.is_in(merged_ids)),
+        )
+        .exec(&txn)
+        .await
+        .map_err(map_db_err)?;
+
+    let status = IssueStatus::merge(merged.iter().chain([&primary]).map(|x| x.status));
+    let merged_count: i32 = merged.iter().map(|x| x.total_count).sum();
+    let first_time = merged.iter().map(|x| x.first_time).min().unwrap();
+    let last_time = merged.iter().map(|x| x.last_time).max().unwrap();
+
+    UploadLog::update_many()
+        .col_expr(
+            upload_log::Column::TotalCount,
+            Expr::col(upload_log::Column::TotalCount).add(merged_count),
+        )
+        .col_expr(
+            upload_log::Column::FirstTime,
+            Expr::cust_with_values("MIN(first_time, ?)", [first_time]),
+        )
+        .col_expr(
+            upload_log::Column::LastTime,
+            Expr::cust_with_values("MAX(last_time, ?)", [last_time]),
+        )
+        .col_expr(upload_log::Column::Status, Expr::value(status))
+        .filter(upload_log::Column::Id.eq(primary.id))
+        .exec(&txn)
+        .await
+        .map_err(map_db_err)?;
+
+    txn.commit().await.map_err(map_db_err)?;
+
+    Ok(HttpResponse::Ok().body("{\"data\": \"ok\"}"))
+}
+
+#[derive(Deserialize, Debug)]
+struct LogUnmergeRequestData {
+    // 已合并的错误的哈希值
+    hash: String,
+}
+
+/// 取消合并, 该哈希值的上报详情归还到原错误
+#[post("/api/log_unmerge")]
+pub async fn api_log_unmerge(
+    req: HttpRequest,
+    credentials: BasicAuth,
+    app_data: web::Data<AppState>,
+    json_data: web::Json<LogUnmergeRequestData>,
+) -> actix_web::Result<HttpResponse> {
+    let user = user_authentication(&req, &credentials, &app_data).await?;
+    if !user.can_write() {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let db = app_data.db_pool.get().unwrap();
+    let txn = db.begin().await.map_err(map_db_err)?;
+    let Some(log) = UploadLog::find()
+        .filter(upload_log::Column::Hash.eq(&json_data.hash))
+        .filter(upload_log::Column::MergedInto.is_not_null())
+        .one(&txn)
+        .await
+        .map_err(map_db_err)?
+        .filter(|x| user.can_view_project(x.project_id))
+    else {
+        return Err(json_error(StatusCode::NOT_FOUND, "merged log not found"));
+    };
+    let Some(primary) = UploadLog::find_by_id(log.merged_into.unwrap_or_default())
+        .one(&txn)
+        .await
+        .map_err(map_db_err)?
+    else {
+        return Err(json_error(StatusCode::NOT_FOUND, "log not found"));
+    };
+
+    UploadOccurrence::update_many()
+        .col_expr(upload_occurrence::Column::LogId, Expr::value(log.id))
+        .filter(upload_occurrence::Column::LogId.eq(primary.id))
+        .filter(upload_occurrence::Column::Hash.eq(&log.hash))
+        .exec(&txn)
+        .await
+        .map_err(map_db_err)?;
+
+    UploadLog::update_many()
+        .col_expr(
+            upload_log::Column::TotalCount,
+            Expr::cust_with_values("MAX(total_count - ?, 0)", [log.total_count]),
+        )
+        .filter(upload_log::Column::Id.eq(primary.id))
+        .exec(&txn)
+        .await
+        .map_err(map_db_err)?;
+
+    let mut log: upload_log::ActiveModel = log.into();
+    log.merged_into = Set(None);
+    log.update(&txn).await.map_err(map_db_err)?;
+
+    txn.commit().await.map_err(map_db_err)?;
+
+    Ok(HttpResponse::Ok().body("{\"data\": \"ok\"}"))
+}
+
+#[derive(Deserialize, Debug)]
+struct LogSplitRequestData {
+    hash: String,
+    // 拆分出去的上报详情, 即 log_content 返回的 user_list 中的 id
+    user_ids: Vec<i32>,
+}
+
+#[derive(Serialize, Debug)]
+struct LogSplitResponseData {
+    // 拆分出的新错误的哈希值
+    hash: String,
+}
+
+/// 将部分上报详情拆分为一个新的错误
+///
+/// 新错误使用随机的哈希值, 之后的上报仍按原哈希值记录到原错误,
+/// 需要区分后续上报时应配合归一化规则或上报时指定的指纹
+#[post("/api/log_split")]
+pub async fn api_log_split(
+    req: HttpRequest,
+    credentials: BasicAuth,
+    app_data: web::Data<AppState>,
+    json_data: web::Json<LogSplitRequestData>,
+) -> actix_web::Result<HttpResponse> {
+    let user = user_authentication(&req, &credentials, &app_data).await?;
+    if !user.can_write() {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let db = app_data.db_pool.get().unwrap();
+    let txn = db.begin().await.map_err(map_db_err)?;
+    let Some(log) = find_issue_by_hash(&txn, &json_data.hash)
+        .await
+        .map_err(map_db_err)?
+        .filter(|x| user.can_view_project(x.project_id))
+    else {
+        return Err(json_error(StatusCode::NOT_FOUND, "log not found"));
+    };
+
+    let occurrences = UploadOccurrence::find()
+        .filter(upload_occurrence::Column::LogId.eq(log.id))
+        .filter(upload_occurrence::Column::UserId.is_in(json_data.user_ids.clone()))
+        .order_by_asc(upload_occurrence::Column::Time)
+        .all(&txn)
+        .await
+        .map_err(map_db_err)?;
+    let (Some(first), Some(last)) = (occurrences.first(), occurrences.last()) else {
+        return Err(json_error(
+            StatusCode::BAD_REQUEST,
+            "no occurrence selected",
+        ));
+    };
+
+    let hash = format!(
+        "{:x}",
+        md5::compute(format!("split-{}-{:032x}", log.id, rand::random::<u128>()))
+    );
+    let count = occurrences.len() as i32;
+
+    let split = upload_log::ActiveModel {
+        id: NotSet,
+        hash: Set(hash.clone()),
+        user_list: Set(String::new()),
+        first_time: Set(first.time),
+        last_time: Set(last.time),
+        total_count: Set(count),
+        status: Set(IssueStatus::Unresolved),
+        resolution_time: Set(log.resolution_time),
+        log_type: Set(log.log_type.clone()),
+        message: Set(log.message.clone()),
+        project_id: Set(log.project_id),
+        fingerprint: Set(None),
+        merged_into: Set(None),
+        muted_until: Set(None),
+        muted_until_count: Set(None),
+    }
+    .insert(&txn)
+    .await
+    .map_err(map_db_err)?;
+
+    UploadOccurrence::update_many()
+        .col_expr(upload_occurrence::Column::LogId, Expr::value(split.id))
+        .filter(upload_occurrence::Column::Id.is_in(occurrences.iter().map(|x| x.id)))
+        .exec(&txn)
+        .await
+        .map_err(map_db_err)?;
+
+    UploadLog::update_many()
+        .col_expr(
+            upload_log::Column::TotalCount,
+            Expr::cust_with_values("MAX(total_count - ?, 0)", [count]),
+        )
+        .filter(upload_log::Column::Id.eq(log.id))
+        .exec(&txn)
+        .await
+        .map_err(map_db_err)?;
+
+    txn.commit().await.map_err(map_db_err)?;
+
+    Ok(HttpResponse::Ok().json(LogSplitResponseData { hash }))
+}
@@ -0,0 +1,375 @@
+use crate::api::{json_error, map_db_err, user_authentication, AppState};
+use crate::orm_entities::prelude::{LogTypeRule, Project, UploadEvent};
+use crate::orm_entities::sea_orm_active_enums::LogAction;
+use crate::orm_entities::{log_type_rule, upload_event};
+use actix_web::http::StatusCode;
+use actix_web::{post, web, HttpRequest, HttpResponse};
+use actix_web_httpauth::extractors::basic::BasicAuth;
+use sea_orm::sea_query::OnConflict;
+use sea_orm::ActiveValue::Set;
+use sea_orm::{
+    ColumnTrait, Condition, ConnectionTrait, DbErr, EntityTrait, NotSet, PaginatorTrait,
+    QueryFilter, QueryOrder,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+/// 按 log_type 决定上报的处理方式
+///
+/// 项目规则优先于项目 0 的全局规则, 同一项目内按最长前缀匹配, 都未匹配时使用默认处理方式
+pub struct LogTypeRules {
+    default_action: LogAction,
+    rules: RwLock<HashMap<i32, Vec<(String, LogAction)>>>,
+}
+
+impl LogTypeRules {
+    pub fn new(default_action: LogAction) -> Self {
+        Self {
+            default_action,
+            rules: Default::default(),
+        }
+    }
+
+    /// 从数据库重新加载规则, 规则变更后调用
+    pub async fn reload<C: ConnectionTrait>(&self, db: &C) -> Result<(), DbErr> {
+        self.replace(LogTypeRule::find().all(db).await?);
+        Ok(())
+    }
+
+    /// 替换全部规则, 同一项目内的规则按前缀长度倒序排列
+    fn replace(&self, models: Vec<log_type_rule::Model>) {
+        let mut rules: HashMap<i32, Vec<(String, LogAction)>> = HashMap::new();
+        for model in models {
+            rules
+                .entry(model.project_id)
+                .or_default()
+                .push((model.log_type, model.action));
+        }
+        for project_rules in rules.values_mut() {
+            project_rules.sort_by_key(|(log_type, _)| std::cmp::Reverse(log_type.len()));
+        }
+
+        *self.rules.write().unwrap() = rules;
+    }
+
+    pub fn action(&self, project_id: i32, log_type: &str) -> LogAction {
+        let rules = self.rules.read().unwrap();
+        let mut project_ids = vec![0];
+        if project_id != 0 {
+            project_ids.insert(0, project_id);
+        }
+
+        project_ids
+            .into_iter()
+            .filter_map(|x| rules.get(&x))
+            .find_map(|project_rules| {
+                project_rules
+                    .iter()
+                    .find(|(prefix, _)| log_type.starts_with(prefix.as_str()))
+                    .map(|(_, action)| *action)
+            })
+            .unwrap_or(self.default_action)
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct LogTypeRuleItemData {
+    id: i32,
+    project_id: i32,
+    log_type: String,
+    action: LogAction,
+}
+
+#[derive(Deserialize, Debug)]
+struct LogTypeRuleListRequestData {
+    #[serde(default)]
+    project_id: i32,
+}
+
+#[post("/api/log_type_rule_list")]
+pub async fn api_log_type_rule_list(
+    req: HttpRequest,
+    credentials: BasicAuth,
+    app_data: web::Data<AppState>,
+    json_data: web::Json<LogTypeRuleListRequestData>,
+) -> actix_web::Result<HttpResponse> {
+    if !user_authentication(&req, &credentials, &app_data)
+        .await?
+        .is_admin()
+    {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let items: Vec<LogTypeRuleItemData> = LogTypeRule::find()
+        .filter(log_type_rule::Column::ProjectId.eq(json_data.project_id))
+        .order_by_asc(log_type_rule::Column::LogType)
+        .all(app_data.db_pool.get().unwrap())
+        .await
+        .map_err(map_db_err)?
+        .into_iter()
+        .map(|x| LogTypeRuleItemData {
+            id: x.id,
+            project_id: x.project_id,
+            log_type: x.log_type,
+            action: x.action,
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(items))
+}
+
+#[derive(Deserialize, Debug)]
+struct LogTypeRuleSaveRequestData {
+    #[serde(default)]
+    project_id: i32,
+    // log_type 前缀
+    log_type: String,
+    action: LogAction,
+}
+
+/// 新增或修改项目中某个 log_type 前缀的处理方式
+#[post("/api/log_type_rule_save")]
+pub async fn api_log_type_rule_save(
+    req: HttpRequest,
+    credentials: BasicAuth,
+    app_data: web::Data<AppState>,
+    json_data: web::Json<LogTypeRuleSaveRequestData>,
+) -> actix_web::Result<HttpResponse> {
+    if !user_authentication(&req, &credentials, &app_data)
+        .await?
+        .is_admin()
+    {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let log_type = json_data.log_type.trim();
+    if log_type.is_empty() {
+        return Err(json_error(StatusCode::BAD_REQUEST, "log_type is empty"));
+    }
+
+    let db = app_data.db_pool.get().unwrap();
+    if json_data.project_id != 0
+        && Project::find_by_id(json_data.project_id)
+            .one(db)
+            .await
+            .map_err(map_db_err)?
+            .is_none()
+    {
+        return Err(json_error(StatusCode::NOT_FOUND, "project not found"));
+    }
+
+    LogTypeRule::insert(log_type_rule::ActiveModel {
+        id: NotSet,
+        project_id: Set(json_data.project_id),
+        log_type: Set(log_type.to_string()),
+        action: Set(json_data.action),
+    })
+    .on_conflict(
+        OnConflict::columns([
+            log_type_rule::Column::ProjectId,
+            log_type_rule::Column::LogType,
+        ])
+        .update_column(log_type_rule::Column::Action)
+        .to_owned(),
+    )
+    .exec_without_returning(db)
+    .await
+    .map_err(map_db_err)?;
+
+    app_data
+        .log_type_rules
+        .reload(db)
+        .await
+        .map_err(map_db_err)?;
+
+    Ok(HttpResponse::Ok().body("{\"data\": \"ok\"}"))
+}
+
+#[derive(Deserialize, Debug)]
+struct LogTypeRuleRemoveRequestData {
+    id: i32,
+}
+
+#[post("/api/log_type_rule_remove")]
+pub async fn api_log_type_rule_remove(
+    req: HttpRequest,
+    credentials: BasicAuth,
+    app_data: web::Data<AppState>,
+    json_data: web::Json<LogTypeRuleRemoveRequestData>,
+) -> actix_web::Result<HttpResponse> {
+    if !user_authentication(&req, &credentials, &app_data)
+        .await?
+        .is_admin()
+    {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let db = app_data.db_pool.get().unwrap();
+    LogTypeRule::delete_by_id(json_data.id)
+        .exec(db)
+        .await
+        .map_err(map_db_err)?;
+
+    app_data
+        .log_type_rules
+        .reload(db)
+        .await
+        .map_err(map_db_err)?;
+
+    Ok(HttpResponse::Ok().body("{\"data\": \"ok\"}"))
+}
+
+#[derive(Deserialize, Debug)]
+struct EventListRequestData {
+    page: u64,
+    page_size: u64,
+    // 为空时不过滤
+    #[serde(default)]
+    log_type: String,
+    project_id: Option<i32>,
+}
+
+#[derive(Serialize, Debug)]
+struct EventItemData {
+    id: i32,
+    project_id: i32,
+    log_type: String,
+    message: String,
+    user: String,
+    package: String,
+    nav_url: String,
+    version: String,
+    logs: String,
+    ip: String,
+    time: i64,
+    receive_time: i64,
+    // 客户端时间减去接收时间的秒数
+    clock_skew: Option<i64>,
+}
+
+#[derive(Serialize, Debug)]
+struct EventListResponseData {
+    success: bool,
+    total: u64,
+    total_pages: u64,
+    items: Vec<EventItemData>,
+}
+
+/// 按时间倒序查询原始事件
+#[post("/api/event_list")]
+pub async fn api_event_list(
+    req: HttpRequest,
+    credentials: BasicAuth,
+    app_data: web::Data<AppState>,
+    json_data: web::Json<EventListRequestData>,
+) -> actix_web::Result<HttpResponse> {
+    let user = user_authentication(&req, &credentials, &app_data).await?;
+
+    let mut condition = Condition::all();
+    if !json_data.log_type.is_empty() {
+        condition = condition.add(upload_event::Column::LogType.eq(&json_data.log_type));
+    }
+    if let Some(project_id) = json_data.project_id {
+        condition = condition.add(upload_event::Column::ProjectId.eq(project_id));
+    }
+    // 只能查看有权限的项目
+    if let Some(projects) = &user.projects {
+        let mut projects = projects.clone();
+        projects.push(0);
+        condition = condition.add(upload_event::Column::ProjectId.is_in(projects));
+    }
+
+    let paginator = UploadEvent::find()
+        .filter(condition)
+        .order_by_desc(upload_event::Column::Time)
+        .order_by_desc(upload_event::Column::Id)
+        .paginate(
+            app_data.db_pool.get().unwrap(),
+            json_data.page_size.clamp(1, 100),
+        );
+    let counts = paginator.num_items_and_pages().await.map_err(map_db_err)?;
+    let items = paginator
+        .fetch_page(json_data.page.max(1) - 1)
+        .await
+        .map_err(map_db_err)?
+        .into_iter()
+        .map(|x| EventItemData {
+            id: x.id,
+            project_id: x.project_id,
+            log_type: x.log_type,
+            message: x.message,
+            user: x.user,
+            package: x.package,
+            nav_url: x.nav_url,
+            version: x.version,
+            logs: x.logs,
+            ip: x.ip,
+            time: x.time.and_utc().timestamp(),
+            receive_time: x.receive_time.and_utc().timestamp(),
+            clock_skew: x.clock_skew,
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(EventListResponseData {
+        success: true,
+        total: counts.number_of_items,
+        total_pages: counts.number_of_pages,
+        items,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(project_id: i32, log_type: &str, action: LogAction) -> log_type_rule::Model {
+        log_type_rule::Model {
+            id: 0,
+            project_id,
+            log_type: log_type.to_string(),
+            action,
+        }
+    }
+
+    /// 同一项目内最长前缀优先, 项目规则优先于全局规则, 都未匹配时使用默认处理方式
+    #[test]
+    fn matches_longest_prefix_and_project_first() {
+        let rules = LogTypeRules::new(LogAction::Issue);
+        rules.replace(vec![
+            rule(0, "debug", LogAction::Drop),
+            rule(0, "metric", LogAction::Event),
+            rule(1, "debug", LogAction::Event),
+            rule(1, "debug.crash", LogAction::Issue),
+            rule(1, "", LogAction::Drop),
+            rule(2, "metric.cpu", LogAction::Drop),
+        ]);
+
+        let cases = [
+            (0, "debug.crash", LogAction::Drop),
+            (0, "metric.cpu", LogAction::Event),
+            (0, "error", LogAction::Issue),
+            (1, "debug.crash.native", LogAction::Issue),
+            (1, "debug.trace", LogAction::Event),
+            // 项目中的空前缀匹配全部, 不再使用全局规则
+            (1, "metric", LogAction::Drop),
+            (2, "metric.cpu", LogAction::Drop),
+            (2, "metric.memory", LogAction::Event),
+            (2, "debug", LogAction::Drop),
+            (2, "error", LogAction::Issue),
+            (3, "metric.cpu", LogAction::Event),
+        ];
+        for (project_id, log_type, expected) in cases {
+            assert_eq!(
+                rules.action(project_id, log_type),
+                expected,
+                "{} {}",
+                project_id,
+                log_type
+            );
+        }
+
+        rules.replace(vec![]);
+        assert_eq!(rules.action(1, "debug"), LogAction::Issue);
+    }
+}
@@ -1,55 +1,381 @@
-use actix_web::{web, HttpRequest};
+use crate::api::account::AuthCache;
+use crate::api::fingerprint::Fingerprinter;
+use crate::api::ingest_queue::IngestQueue;
+use crate::api::log_type::LogTypeRules;
+use crate::api::rate_limit::RateLimiter;
+use crate::api::spike::SpikeDetector;
+use crate::api::version::VersionSchemes;
+use crate::orm_entities::account_project;
+use crate::orm_entities::prelude::{Account, AccountProject};
+use crate::orm_entities::sea_orm_active_enums::Role;
+use actix_web::error::{InternalError, JsonPayloadError};
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpRequest, HttpResponse};
 use actix_web_httpauth::extractors::basic::{BasicAuth, Config};
-use sea_orm::DatabaseConnection;
-use std::sync::Arc;
+use chrono::{DateTime, NaiveDateTime};
+use rand::Rng;
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, PaginatorTrait, QueryFilter};
+use std::sync::{Arc, Mutex};
 use tokio::sync::OnceCell;
 
+pub mod account;
+pub mod configuration_info;
+pub mod fingerprint;
+pub mod grouping;
+pub mod grouping_html;
+pub mod ingest_queue;
+pub mod issue_status;
 pub mod log;
 pub mod log_html;
+pub mod log_merge;
+pub mod log_type;
+pub mod otlp;
+pub mod project;
+pub mod project_html;
 pub mod query_ip;
+pub mod rate_limit;
+pub mod regroup;
+pub mod release;
+pub mod sentry;
+pub mod spike;
 pub mod statistics;
+pub mod statistics_device;
 pub mod statistics_html;
+pub mod version;
 
 #[derive(Clone)]
 pub struct AppState {
-    pub username: Arc<String>,
-    pub password: Arc<String>,
-    pub admin_account: Arc<String>,
-    pub admin_password: Arc<String>,
     pub db_pool: Arc<OnceCell<DatabaseConnection>>,
+    pub sampling: SamplingPolicy,
+    pub clock: ClockPolicy,
+    pub require_ingest_key: bool,
+    pub auth_cache: Arc<Mutex<AuthCache>>,
+    pub fingerprinter: Arc<Fingerprinter>,
+    pub log_type_rules: Arc<LogTypeRules>,
+    pub rate_limiter: Arc<RateLimiter>,
+    pub spike_detector: Arc<SpikeDetector>,
+    pub version_schemes: Arc<VersionSchemes>,
+    /// 为空时上报在请求中同步写入
+    pub ingest_queue: Option<IngestQueue>,
+}
+
+/// 错误上报用户详情的采样策略
+#[derive(Clone, Copy, Debug)]
+pub struct SamplingPolicy {
+    /// 每个错误完整保存的前 N 条上报详情
+    pub keep_first: u64,
+    /// 超过 keep_first 之后每 K 次上报保存一条, 0 表示不再保存
+    pub sample_every: u64,
+    /// 超过 keep_first 之后按包名及版本分别保留的蓄水池大小, 0 表示不使用蓄水池采样;
+    /// 使用蓄水池时每 K 次上报参与一次采样, K 为 0 时每次上报都参与
+    pub reservoir: u64,
+}
+
+impl SamplingPolicy {
+    /// `stored` 为该错误已保存的详情数量, `total` 为包含本次在内的上报总数
+    pub fn should_keep(&self, stored: u64, total: u64) -> bool {
+        if stored < self.keep_first {
+            return true;
+        }
+        self.sample_every > 0 && total.is_multiple_of(self.sample_every)
+    }
+
+    /// 蓄水池采样, `seen` 为超过 keep_first 之后同一包名及版本包含本次在内的上报次数
+    ///
+    /// 返回本次上报在蓄水池中的位置, 该位置已有上报详情时替换, None 表示不保存
+    pub fn reservoir_slot(&self, seen: u64) -> Option<u64> {
+        let every = self.sample_every.max(1);
+        if self.reservoir == 0 || seen == 0 || !seen.is_multiple_of(every) {
+            return None;
+        }
+        let candidate = seen / every;
+        if candidate <= self.reservoir {
+            return Some(candidate - 1);
+        }
+        let slot = rand::thread_rng().gen_range(0..candidate);
+        (slot < self.reservoir).then_some(slot)
+    }
+}
+
+/// 客户端上报时间的校验策略
+#[derive(Clone, Copy, Debug)]
+pub struct ClockPolicy {
+    /// 客户端时间最多可以早于接收时间的秒数, 即离线缓存的最长时间
+    pub max_lag: i64,
+}
+
+/// 一次上报的时间
+#[derive(Clone, Copy, Debug)]
+pub struct ReportTime {
+    /// 记录使用的时间
+    pub time: NaiveDateTime,
+    /// 服务器接收时间
+    pub receive_time: NaiveDateTime,
+    /// 客户端时间减去接收时间的秒数, 客户端未提供时间时为 None
+    pub clock_skew: Option<i64>,
+}
+
+impl ClockPolicy {
+    /// `client_time` 为客户端上报的 unix 时间戳(秒)
+    ///
+    /// 客户端时间晚于接收时间, 或早于接收时间超过 max_lag 时认为客户端时钟异常, 使用接收时间
+    pub fn resolve(&self, client_time: Option<i64>, receive_time: NaiveDateTime) -> ReportTime {
+        let clock_skew =
+            client_time.map(|x| x.saturating_sub(receive_time.and_utc().timestamp()));
+        let time = match (client_time, clock_skew) {
+            (Some(client_time), Some(skew))
+                if skew <= 0 && skew >= self.max_lag.saturating_neg() =>
+            {
+                DateTime::from_timestamp(client_time, 0)
+                    .map(|x| x.naive_utc())
+                    .unwrap_or(receive_time)
+            }
+            _ => receive_time,
+        };
+        ReportTime {
+            time,
+            receive_time,
+            clock_skew,
+        }
+    }
 }
 
 pub fn map_db_err(err: sea_orm::DbErr) -> actix_web::Error {
     actix_web::error::ErrorInternalServerError(format!("sqlx error:{}", err))
 }
 
+/// 以 `{"error": "..."}` 形式返回的错误
+pub fn json_error(status: StatusCode, message: &str) -> actix_web::Error {
+    InternalError::from_response(
+        message.to_string(),
+        HttpResponse::build(status).json(serde_json::json!({ "error": message })),
+    )
+    .into()
+}
+
+/// JSON 请求体配置
+///
+/// `Content-Encoding` 为 gzip / deflate / br 的请求体由 actix 解压, `max_size` 限制的是解压后的大小,
+/// 超出时返回 413
+pub fn json_config(max_size: usize) -> web::JsonConfig {
+    web::JsonConfig::default()
+        .limit(max_size)
+        .error_handler(|err, _req| match err {
+            JsonPayloadError::OverflowKnownLength { .. } | JsonPayloadError::Overflow { .. } => {
+                json_error(StatusCode::PAYLOAD_TOO_LARGE, &err.to_string())
+            }
+            _ => json_error(StatusCode::BAD_REQUEST, &err.to_string()),
+        })
+}
+
+/// 已通过鉴权的用户
+#[derive(Clone, Debug)]
+pub struct AuthUser {
+    /// 账号名, 未创建任何账号时为空
+    pub username: String,
+    pub role: Role,
+    /// 可访问的项目id, None 表示不限制
+    pub projects: Option<Vec<i32>>,
+}
+
+impl AuthUser {
+    pub fn is_admin(&self) -> bool {
+        self.role == Role::Admin
+    }
+
+    /// 是否可以修改错误状态
+    pub fn can_write(&self) -> bool {
+        matches!(self.role, Role::Developer | Role::Admin)
+    }
+
+    /// 未指定项目(0)的数据所有人可见
+    pub fn can_view_project(&self, project_id: i32) -> bool {
+        project_id == 0
+            || self
+                .projects
+                .as_ref()
+                .is_none_or(|projects| projects.contains(&project_id))
+    }
+}
+
 // 用户鉴权
 pub async fn user_authentication(
     req: &HttpRequest,
     credentials: &BasicAuth,
     app_data: &web::Data<AppState>,
-) -> actix_web::Result<bool> {
-    let username = credentials.user_id();
-    let password = credentials.password().unwrap_or_default();
-
-    if !app_data.admin_account.is_empty() || !app_data.admin_password.is_empty() {
-        // 验证用户名和密码
-        if username == app_data.admin_account.as_str()
-            && password == app_data.admin_password.as_str()
-        {
-            return Ok(true);
-        };
+) -> actix_web::Result<AuthUser> {
+    let db = app_data.db_pool.get().unwrap();
+
+    // 尚未创建任何账号时不做鉴权, 但只能查看, 创建管理员账号之后才能修改
+    if Account::find().count(db).await.map_err(map_db_err)? == 0 {
+        return Ok(AuthUser {
+            username: String::new(),
+            role: Role::Viewer,
+            projects: None,
+        });
     }
 
-    if !app_data.username.is_empty() || !app_data.password.is_empty() {
-        // 验证用户名和密码
-        return if username == app_data.username.as_str() && password == app_data.password.as_str() {
-            Ok(false)
+    let username = credentials.user_id();
+    let password = credentials.password().unwrap_or_default().to_string();
+
+    let account = account::find_account(db, username)
+        .await
+        .map_err(map_db_err)?
+        .filter(|x| !x.disabled);
+
+    let verified = if let Some(account) = &account {
+        // 缓存验证通过的密码, 避免每次请求都计算 Argon2
+        let cache_key = app_data
+            .auth_cache
+            .lock()
+            .unwrap()
+            .key(&account.password_hash, &password);
+        if app_data.auth_cache.lock().unwrap().contains(&cache_key) {
+            true
         } else {
+            let password_hash = account.password_hash.clone();
+            let verified =
+                web::block(move || account::verify_password(&password, &password_hash)).await?;
+            if verified {
+                app_data.auth_cache.lock().unwrap().insert(cache_key);
+            }
+            verified
+        }
+    } else {
+        false
+    };
+
+    match account {
+        Some(account) if verified => {
+            let projects = if account.role == Role::Admin {
+                None
+            } else {
+                Some(
+                    AccountProject::find()
+                        .filter(account_project::Column::AccountId.eq(account.id))
+                        .all(db)
+                        .await
+                        .map_err(map_db_err)?
+                        .into_iter()
+                        .map(|x| x.project_id)
+                        .collect(),
+                )
+            };
+            Ok(AuthUser {
+                username: account.username,
+                role: account.role,
+                projects,
+            })
+        }
+        _ => {
             let config = req.app_data::<Config>().cloned().unwrap_or_default();
             Err(actix_web_httpauth::extractors::AuthenticationError::from(config).into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 前 keep_first 条全部保存, 之后每 sample_every 次保存一条
+    #[test]
+    fn sampling_keeps_first_then_every_k() {
+        let policy = SamplingPolicy {
+            keep_first: 3,
+            sample_every: 10,
+            reservoir: 0,
         };
+        let cases = [
+            (0, 1, true),
+            (2, 3, true),
+            (3, 4, false),
+            (3, 10, true),
+            (4, 11, false),
+            (4, 20, true),
+        ];
+        for (stored, total, expected) in cases {
+            assert_eq!(
+                policy.should_keep(stored, total),
+                expected,
+                "{} {}",
+                stored,
+                total
+            );
+        }
+
+        let policy = SamplingPolicy {
+            sample_every: 0,
+            ..policy
+        };
+        assert!(policy.should_keep(2, 1000));
+        assert!(!policy.should_keep(3, 1000));
+    }
+
+    /// 蓄水池未满时依次填充, 之后以 reservoir / candidate 的概率替换
+    #[test]
+    fn reservoir_fills_then_replaces() {
+        let policy = SamplingPolicy {
+            keep_first: 0,
+            sample_every: 5,
+            reservoir: 2,
+        };
+        assert_eq!(policy.reservoir_slot(0), None);
+        assert_eq!(policy.reservoir_slot(3), None);
+        assert_eq!(policy.reservoir_slot(5), Some(0));
+        assert_eq!(policy.reservoir_slot(10), Some(1));
+        for _ in 0..100 {
+            assert!(policy.reservoir_slot(15).is_none_or(|x| x < 2));
+        }
+
+        let policy = SamplingPolicy {
+            sample_every: 0,
+            ..policy
+        };
+        assert_eq!(policy.reservoir_slot(1), Some(0));
+        assert_eq!(policy.reservoir_slot(2), Some(1));
+        let kept = (0..10000)
+            .filter(|_| policy.reservoir_slot(10).is_some())
+            .count();
+        assert!((1500..2500).contains(&kept), "{}", kept);
+
+        let policy = SamplingPolicy {
+            reservoir: 0,
+            ..policy
+        };
+        assert_eq!(policy.reservoir_slot(1), None);
     }
 
-    Ok(false)
+    /// 客户端时间晚于接收时间或落后超过 max_lag 时使用接收时间
+    #[test]
+    fn clock_policy_resolves_client_time() {
+        let policy = ClockPolicy { max_lag: 3600 };
+        let receive_time = DateTime::from_timestamp(1_700_000_000, 0)
+            .unwrap()
+            .naive_utc();
+        let at = |x: i64| DateTime::from_timestamp(x, 0).unwrap().naive_utc();
+
+        let cases = [
+            (None, receive_time, None),
+            (Some(1_700_000_000), receive_time, Some(0)),
+            (Some(1_699_999_000), at(1_699_999_000), Some(-1000)),
+            (Some(1_699_996_400), at(1_699_996_400), Some(-3600)),
+            (Some(1_699_996_399), receive_time, Some(-3601)),
+            (Some(1_700_000_001), receive_time, Some(1)),
+            (Some(i64::MAX), receive_time, Some(i64::MAX - 1_700_000_000)),
+            (Some(i64::MIN), receive_time, Some(i64::MIN)),
+        ];
+        for (client_time, time, clock_skew) in cases {
+            let resolved = policy.resolve(client_time, receive_time);
+            assert_eq!(resolved.time, time, "{:?}", client_time);
+            assert_eq!(resolved.receive_time, receive_time);
+            assert_eq!(resolved.clock_skew, clock_skew, "{:?}", client_time);
+        }
+
+        let policy = ClockPolicy { max_lag: i64::MIN };
+        assert_eq!(
+            policy.resolve(Some(i64::MIN), receive_time).time,
+            receive_time
+        );
+    }
 }
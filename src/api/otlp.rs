@@ -0,0 +1,801 @@
+use crate::api::json_error;
+use crate::api::log::{client_ip, submit_log, UploadLogData};
+use crate::api::project::authorize_ingest;
+use crate::api::AppState;
+use actix_web::http::header::CONTENT_TYPE;
+use actix_web::http::StatusCode;
+use actix_web::{post, web, HttpRequest, HttpResponse};
+use serde_json::{json, Value};
+
+// SeverityNumber 中 ERROR 的起始值, 17~20 为 ERROR, 21~24 为 FATAL
+const SEVERITY_ERROR: u64 = 17;
+
+// 作为包名/版本/用户等字段使用的属性, 不再重复写入 logs
+const MAPPED_ATTRIBUTES: [&str; 8] = [
+    "service.name",
+    "service.version",
+    "enduser.id",
+    "url.full",
+    "http.url",
+    "exception.type",
+    "exception.message",
+    "exception.stacktrace",
+];
+
+/// protobuf 编码的 `ExportLogsServiceRequest` 解码, 结果为与 OTLP/JSON 相同结构的 JSON,
+/// 以及 `ExportLogsServiceResponse` 编码
+///
+/// 只解析日志相关的字段, 其他字段忽略
+mod proto {
+    use serde_json::{json, Map, Value};
+
+    enum Wire<'a> {
+        Varint(u64),
+        Fixed64(u64),
+        Bytes(&'a [u8]),
+        Fixed32,
+        Group,
+    }
+
+    struct Reader<'a> {
+        buf: &'a [u8],
+        pos: usize,
+    }
+
+    impl<'a> Reader<'a> {
+        fn new(buf: &'a [u8]) -> Self {
+            Self { buf, pos: 0 }
+        }
+
+        fn varint(&mut self) -> Result<u64, String> {
+            let mut value = 0;
+            for shift in (0..64).step_by(7) {
+                let byte = *self.buf.get(self.pos).ok_or("truncated varint")?;
+                self.pos += 1;
+                value |= ((byte & 0x7f) as u64) << shift;
+                if byte & 0x80 == 0 {
+                    return Ok(value);
+                }
+            }
+            Err("varint too long".into())
+        }
+
+        fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+            let end = self
+                .pos
+                .checked_add(len)
+                .filter(|x| *x <= self.buf.len())
+                .ok_or("truncated field")?;
+            let bytes = &self.buf[self.pos..end];
+            self.pos = end;
+            Ok(bytes)
+        }
+
+        /// 跳过已废弃的 group 直到对应的结束标记, 嵌套的 group 一并跳过
+        fn skip_group(&mut self, number: u64) -> Result<(), String> {
+            let mut open = vec![number];
+            while let Some(&expected) = open.last() {
+                if self.pos >= self.buf.len() {
+                    return Err("truncated group".into());
+                }
+                let tag = self.varint()?;
+                match tag & 7 {
+                    0 => {
+                        self.varint()?;
+                    }
+                    1 => {
+                        self.take(8)?;
+                    }
+                    2 => {
+                        let len = self.varint()? as usize;
+                        self.take(len)?;
+                    }
+                    3 => open.push(tag >> 3),
+                    4 if tag >> 3 == expected => {
+                        open.pop();
+                    }
+                    5 => {
+                        self.take(4)?;
+                    }
+                    x => return Err(format!("unexpected wire type {} in group", x)),
+                }
+            }
+            Ok(())
+        }
+
+        /// 读取下一个字段, 返回字段编号及值
+        fn field(&mut self) -> Result<Option<(u64, Wire<'a>)>, String> {
+            if self.pos >= self.buf.len() {
+                return Ok(None);
+            }
+            let tag = self.varint()?;
+            let wire = match tag & 7 {
+                0 => Wire::Varint(self.varint()?),
+                1 => Wire::Fixed64(u64::from_le_bytes(self.take(8)?.try_into().unwrap())),
+                2 => {
+                    let len = self.varint()? as usize;
+                    Wire::Bytes(self.take(len)?)
+                }
+                3 => {
+                    self.skip_group(tag >> 3)?;
+                    Wire::Group
+                }
+                5 => {
+                    self.take(4)?;
+                    Wire::Fixed32
+                }
+                x => return Err(format!("unsupported wire type {}", x)),
+            };
+            Ok(Some((tag >> 3, wire)))
+        }
+    }
+
+    fn string(bytes: &[u8]) -> Value {
+        Value::String(String::from_utf8_lossy(bytes).into_owned())
+    }
+
+    fn hex(bytes: &[u8]) -> Value {
+        Value::String(bytes.iter().map(|x| format!("{:02x}", x)).collect())
+    }
+
+    fn push(object: &mut Map<String, Value>, name: &str, value: Value) {
+        if let Value::Array(items) = object.entry(name).or_insert_with(|| Value::Array(vec![])) {
+            items.push(value);
+        }
+    }
+
+    /// 数组及键值列表的嵌套层数, 超过时拒绝, 避免递归解析时栈溢出
+    pub(super) const MAX_DEPTH: usize = 32;
+
+    fn any_value(buf: &[u8], depth: usize) -> Result<Value, String> {
+        if depth > MAX_DEPTH {
+            return Err("AnyValue nested too deeply".into());
+        }
+        let mut reader = Reader::new(buf);
+        let mut value = json!({});
+        while let Some((number, wire)) = reader.field()? {
+            value = match (number, wire) {
+                (1, Wire::Bytes(x)) => json!({ "stringValue": string(x) }),
+                (2, Wire::Varint(x)) => json!({ "boolValue": x != 0 }),
+                (3, Wire::Varint(x)) => json!({ "intValue": (x as i64).to_string() }),
+                (4, Wire::Fixed64(x)) => json!({ "doubleValue": f64::from_bits(x) }),
+                (5, Wire::Bytes(x)) => json!({ "arrayValue": values(x, depth + 1, any_value)? }),
+                (6, Wire::Bytes(x)) => json!({ "kvlistValue": values(x, depth + 1, key_value)? }),
+                (7, Wire::Bytes(x)) => json!({ "bytesValue": hex(x) }),
+                _ => continue,
+            };
+        }
+        Ok(value)
+    }
+
+    /// `ArrayValue` / `KeyValueList`, 字段 1 为重复的元素
+    fn values(
+        buf: &[u8],
+        depth: usize,
+        item: fn(&[u8], usize) -> Result<Value, String>,
+    ) -> Result<Value, String> {
+        let mut reader = Reader::new(buf);
+        let mut object = Map::new();
+        object.insert("values".into(), Value::Array(vec![]));
+        while let Some((number, wire)) = reader.field()? {
+            if let (1, Wire::Bytes(x)) = (number, wire) {
+                push(&mut object, "values", item(x, depth)?);
+            }
+        }
+        Ok(Value::Object(object))
+    }
+
+    fn key_value(buf: &[u8], depth: usize) -> Result<Value, String> {
+        let mut reader = Reader::new(buf);
+        let mut object = Map::new();
+        while let Some((number, wire)) = reader.field()? {
+            match (number, wire) {
+                (1, Wire::Bytes(x)) => {
+                    object.insert("key".into(), string(x));
+                }
+                (2, Wire::Bytes(x)) => {
+                    object.insert("value".into(), any_value(x, depth)?);
+                }
+                _ => {}
+            }
+        }
+        Ok(Value::Object(object))
+    }
+
+    /// `Resource` / `InstrumentationScope` 中的属性列表
+    fn attributes(buf: &[u8], field: u64) -> Result<Value, String> {
+        let mut reader = Reader::new(buf);
+        let mut object = Map::new();
+        while let Some((number, wire)) = reader.field()? {
+            match (number, wire) {
+                (n, Wire::Bytes(x)) if n == field => {
+                    push(&mut object, "attributes", key_value(x, 0)?)
+                }
+                (1, Wire::Bytes(x)) => {
+                    object.insert("name".into(), string(x));
+                }
+                (2, Wire::Bytes(x)) => {
+                    object.insert("version".into(), string(x));
+                }
+                _ => {}
+            }
+        }
+        Ok(Value::Object(object))
+    }
+
+    fn log_record(buf: &[u8]) -> Result<Value, String> {
+        let mut reader = Reader::new(buf);
+        let mut object = Map::new();
+        while let Some((number, wire)) = reader.field()? {
+            let (name, value) = match (number, wire) {
+                (1, Wire::Fixed64(x)) => ("timeUnixNano", Value::String(x.to_string())),
+                (11, Wire::Fixed64(x)) => ("observedTimeUnixNano", Value::String(x.to_string())),
+                (2, Wire::Varint(x)) => ("severityNumber", json!(x)),
+                (3, Wire::Bytes(x)) => ("severityText", string(x)),
+                (5, Wire::Bytes(x)) => ("body", any_value(x, 0)?),
+                (6, Wire::Bytes(x)) => {
+                    push(&mut object, "attributes", key_value(x, 0)?);
+                    continue;
+                }
+                (9, Wire::Bytes(x)) => ("traceId", hex(x)),
+                (10, Wire::Bytes(x)) => ("spanId", hex(x)),
+                (12, Wire::Bytes(x)) => ("eventName", string(x)),
+                _ => continue,
+            };
+            object.insert(name.into(), value);
+        }
+        Ok(Value::Object(object))
+    }
+
+    fn scope_logs(buf: &[u8]) -> Result<Value, String> {
+        let mut reader = Reader::new(buf);
+        let mut object = Map::new();
+        while let Some((number, wire)) = reader.field()? {
+            match (number, wire) {
+                // InstrumentationScope 的属性为字段 3
+                (1, Wire::Bytes(x)) => {
+                    object.insert("scope".into(), attributes(x, 3)?);
+                }
+                (2, Wire::Bytes(x)) => push(&mut object, "logRecords", log_record(x)?),
+                _ => {}
+            }
+        }
+        Ok(Value::Object(object))
+    }
+
+    fn resource_logs(buf: &[u8]) -> Result<Value, String> {
+        let mut reader = Reader::new(buf);
+        let mut object = Map::new();
+        while let Some((number, wire)) = reader.field()? {
+            match (number, wire) {
+                (1, Wire::Bytes(x)) => {
+                    object.insert("resource".into(), attributes(x, 1)?);
+                }
+                // 1000 为旧版本的 instrumentation_library_logs, 结构相同
+                (2 | 1000, Wire::Bytes(x)) => push(&mut object, "scopeLogs", scope_logs(x)?),
+                _ => {}
+            }
+        }
+        Ok(Value::Object(object))
+    }
+
+    pub fn decode_logs_request(buf: &[u8]) -> Result<Value, String> {
+        let mut reader = Reader::new(buf);
+        let mut object = Map::new();
+        object.insert("resourceLogs".into(), Value::Array(vec![]));
+        while let Some((number, wire)) = reader.field()? {
+            if let (1, Wire::Bytes(x)) = (number, wire) {
+                push(&mut object, "resourceLogs", resource_logs(x)?);
+            }
+        }
+        Ok(Value::Object(object))
+    }
+
+    fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+        while value >= 0x80 {
+            out.push((value as u8 & 0x7f) | 0x80);
+            value >>= 7;
+        }
+        out.push(value as u8);
+    }
+
+    /// 只包含 `partial_success` 的 `ExportLogsServiceResponse`
+    pub fn encode_partial_success(rejected: u64, message: &str) -> Vec<u8> {
+        let mut partial = vec![];
+        write_varint(&mut partial, 1 << 3);
+        write_varint(&mut partial, rejected);
+        write_varint(&mut partial, (2 << 3) | 2);
+        write_varint(&mut partial, message.len() as u64);
+        partial.extend_from_slice(message.as_bytes());
+
+        let mut out = vec![];
+        write_varint(&mut out, (1 << 3) | 2);
+        write_varint(&mut out, partial.len() as u64);
+        out.extend(partial);
+        out
+    }
+}
+
+/// JSON 中的数组字段, 不存在时为空
+fn array<'a>(value: &'a Value, pointer: &str) -> &'a [Value] {
+    value
+        .pointer(pointer)
+        .and_then(Value::as_array)
+        .map_or(&[], |x| x.as_slice())
+}
+
+/// `AnyValue` 转为文本
+fn any_value_text(value: &Value) -> String {
+    let Some((kind, value)) = value.as_object().and_then(|x| x.iter().next()) else {
+        return String::new();
+    };
+    match kind.as_str() {
+        "arrayValue" => {
+            let items: Vec<String> = array(value, "/values").iter().map(any_value_text).collect();
+            format!("[{}]", items.join(", "))
+        }
+        "kvlistValue" => {
+            let items: Vec<String> = attributes(array(value, "/values"))
+                .into_iter()
+                .map(|(key, value)| format!("{}: {}", key, value))
+                .collect();
+            format!("{{{}}}", items.join(", "))
+        }
+        _ => match value {
+            Value::String(x) => x.clone(),
+            x => x.to_string(),
+        },
+    }
+}
+
+/// `KeyValue` 列表转为 (key, 文本值)
+fn attributes(list: &[Value]) -> Vec<(&str, String)> {
+    list.iter()
+        .filter_map(|x| {
+            let key = x.get("key")?.as_str()?;
+            Some((key, x.get("value").map(any_value_text).unwrap_or_default()))
+        })
+        .collect()
+}
+
+/// 时间戳字段(纳秒), JSON 中可能为字符串或数字
+fn nanos(value: &Value, name: &str) -> Option<u64> {
+    match value.get(name)? {
+        Value::String(x) => x.parse().ok(),
+        x => x.as_u64(),
+    }
+    .filter(|x| *x > 0)
+}
+
+/// 是否为 ERROR 及以上级别, 未指定 severityNumber 时根据 severityText 判断
+fn is_error(record: &Value) -> bool {
+    let number = match record.get("severityNumber") {
+        Some(Value::String(x)) => match x.trim_start_matches("SEVERITY_NUMBER_") {
+            "ERROR" | "ERROR2" | "ERROR3" | "ERROR4" => SEVERITY_ERROR,
+            "FATAL" | "FATAL2" | "FATAL3" | "FATAL4" => SEVERITY_ERROR + 4,
+            _ => 0,
+        },
+        Some(x) => x.as_u64().unwrap_or_default(),
+        None => 0,
+    };
+    if number != 0 {
+        return number >= SEVERITY_ERROR;
+    }
+
+    let text = record
+        .get("severityText")
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_ascii_uppercase();
+    ["ERROR", "FATAL", "CRITICAL", "ALERT", "EMERG", "PANIC"]
+        .iter()
+        .any(|x| text.starts_with(x))
+}
+
+/// 将一条日志记录转换为上报数据
+///
+/// 有 `exception.*` 属性时使用异常类型及调用栈作为消息, 否则使用 body
+fn upload_log_data(resource: &[(&str, String)], scope: &Value, record: &Value) -> UploadLogData {
+    let record_attributes = attributes(array(record, "/attributes"));
+    let find = |name: &str| {
+        record_attributes
+            .iter()
+            .chain(resource)
+            .find(|(key, value)| *key == name && !value.is_empty())
+            .map(|(_, value)| value.as_str())
+    };
+
+    let body = record.get("body").map(any_value_text).unwrap_or_default();
+    let exception = match (find("exception.type"), find("exception.message")) {
+        (Some(kind), Some(message)) => Some(format!("{}: {}", kind, message)),
+        (Some(x), None) | (None, Some(x)) => Some(x.to_string()),
+        (None, None) => None,
+    };
+    let stacktrace = find("exception.stacktrace");
+
+    let mut logs = vec![];
+    if (exception.is_some() || stacktrace.is_some()) && !body.is_empty() {
+        logs.push(body.clone());
+    }
+    let message = match (exception, stacktrace) {
+        (None, None) => body,
+        // 多数语言的调用栈文本已包含异常类型及消息
+        (Some(head), Some(stacktrace)) if stacktrace.starts_with(&head) => stacktrace.to_string(),
+        (head, stacktrace) => head
+            .into_iter()
+            .chain(stacktrace.map(str::to_string))
+            .collect::<Vec<_>>()
+            .join("\n"),
+    };
+
+    if let Some(name) = scope.get("name").and_then(Value::as_str) {
+        logs.push(format!("scope: {}", name));
+    }
+    for name in ["traceId", "spanId", "eventName"] {
+        if let Some(value) = record.get(name).and_then(Value::as_str) {
+            if !value.is_empty() {
+                logs.push(format!("{}: {}", name, value));
+            }
+        }
+    }
+    logs.extend(
+        record_attributes
+            .iter()
+            .chain(resource)
+            .filter(|(key, _)| !MAPPED_ATTRIBUTES.contains(key))
+            .map(|(key, value)| format!("{}: {}", key, value)),
+    );
+
+    UploadLogData {
+        log_type: "error".to_string(),
+        message,
+        user: ["enduser.id", "service.instance.id", "host.name"]
+            .into_iter()
+            .find_map(find)
+            .unwrap_or_default()
+            .to_string(),
+        package: find("service.name").unwrap_or_default().to_string(),
+        nav_url: find("url.full")
+            .or_else(|| find("http.url"))
+            .unwrap_or_default()
+            .to_string(),
+        version: find("service.version").unwrap_or_default().to_string(),
+        logs: logs.join("\n"),
+        fingerprint: None,
+        time: nanos(record, "timeUnixNano")
+            .or_else(|| nanos(record, "observedTimeUnixNano"))
+            .map(|x| (x / 1_000_000_000) as i64),
+    }
+}
+
+/// 取出请求中 ERROR 及以上级别的日志
+fn error_logs(request: &Value) -> Vec<UploadLogData> {
+    let mut result = vec![];
+    for resource_logs in array(request, "/resourceLogs") {
+        let resource = attributes(array(resource_logs, "/resource/attributes"));
+        for scope_logs in array(resource_logs, "/scopeLogs") {
+            let scope = scope_logs.get("scope").unwrap_or(&Value::Null);
+            for record in array(scope_logs, "/logRecords") {
+                if is_error(record) {
+                    result.push(upload_log_data(&resource, scope, record));
+                }
+            }
+        }
+    }
+    result
+}
+
+/// OTLP/HTTP 日志上报, 支持 protobuf 及 JSON 编码
+///
+/// 只保存 ERROR 及以上级别的日志, 资源属性 service.name / service.version 作为包名及版本
+#[post("/v1/logs")]
+pub async fn api_otlp_logs(
+    req: HttpRequest,
+    app_data: web::Data<AppState>,
+    body: web::Bytes,
+) -> actix_web::Result<HttpResponse> {
+    app_data.rate_limiter.check(&req, [], 1)?;
+    let project_id = authorize_ingest(&req, &app_data).await?;
+
+    let is_json = req
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|x| x.to_str().ok())
+        .is_some_and(|x| x.starts_with("application/json"));
+
+    let request = if is_json {
+        serde_json::from_slice(&body).map_err(|err| err.to_string())
+    } else {
+        proto::decode_logs_request(&body)
+    }
+    .map_err(|err| json_error(StatusCode::BAD_REQUEST, &err))?;
+
+    let mut logs = error_logs(&request);
+    let count = app_data.rate_limiter.check_items(
+        &req,
+        logs.iter().map(|x| x.package.as_str()),
+        logs.len(),
+    )?;
+    // 超出限流的日志丢弃, 丢弃的数量在响应的 partial_success 中返回
+    let rejected = logs.len() - count;
+    logs.truncate(count);
+
+    let ip = client_ip(&req);
+    for data in logs {
+        submit_log(&app_data, project_id, &ip, data).await?;
+    }
+
+    // 全部接收时响应为空的 ExportLogsServiceResponse
+    Ok(match (is_json, rejected) {
+        (true, 0) => HttpResponse::Ok().json(json!({})),
+        (true, rejected) => HttpResponse::Ok().json(json!({
+            "partialSuccess": {
+                "rejectedLogRecords": rejected.to_string(),
+                "errorMessage": "too many requests",
+            }
+        })),
+        (false, 0) => HttpResponse::Ok()
+            .content_type("application/x-protobuf")
+            .finish(),
+        (false, rejected) => HttpResponse::Ok()
+            .content_type("application/x-protobuf")
+            .body(proto::encode_partial_success(
+                rejected as u64,
+                "too many requests",
+            )),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn varint(mut value: u64) -> Vec<u8> {
+        let mut out = vec![];
+        while value >= 0x80 {
+            out.push((value as u8 & 0x7f) | 0x80);
+            value >>= 7;
+        }
+        out.push(value as u8);
+        out
+    }
+
+    fn tag(number: u64, wire: u64) -> Vec<u8> {
+        varint((number << 3) | wire)
+    }
+
+    fn uint(number: u64, value: u64) -> Vec<u8> {
+        [tag(number, 0), varint(value)].concat()
+    }
+
+    fn fixed64(number: u64, value: u64) -> Vec<u8> {
+        [tag(number, 1), value.to_le_bytes().to_vec()].concat()
+    }
+
+    fn bytes(number: u64, data: &[u8]) -> Vec<u8> {
+        [tag(number, 2), varint(data.len() as u64), data.to_vec()].concat()
+    }
+
+    fn key_value(key: &str, value: &str) -> Vec<u8> {
+        [
+            bytes(1, key.as_bytes()),
+            bytes(2, &bytes(1, value.as_bytes())),
+        ]
+        .concat()
+    }
+
+    /// 包含一个资源及一个 scope 的 `ExportLogsServiceRequest`
+    fn request(records: &[Vec<u8>]) -> Vec<u8> {
+        let resource = [
+            bytes(1, &key_value("service.name", "app")),
+            bytes(1, &key_value("service.version", "1.2.0")),
+        ]
+        .concat();
+        let mut scope_logs = bytes(1, &bytes(1, b"lib"));
+        for record in records {
+            scope_logs.extend(bytes(2, record));
+        }
+        let resource_logs = [bytes(1, &resource), bytes(2, &scope_logs)].concat();
+        bytes(1, &resource_logs)
+    }
+
+    fn error_record() -> Vec<u8> {
+        [
+            fixed64(1, 1_700_000_000_123_000_000),
+            uint(2, 17),
+            bytes(5, &bytes(1, b"boom")),
+            bytes(6, &key_value("exception.type", "TypeError")),
+            bytes(6, &key_value("http.method", "GET")),
+            bytes(9, &[0xab, 0xcd]),
+        ]
+        .concat()
+    }
+
+    /// 手工编码的最小请求, 只保留 ERROR 及以上级别的日志
+    #[test]
+    fn decodes_minimal_protobuf_request() {
+        let info = [uint(2, 9), bytes(5, &bytes(1, b"fine"))].concat();
+        let decoded = proto::decode_logs_request(&request(&[error_record(), info])).unwrap();
+
+        let record = &decoded["resourceLogs"][0]["scopeLogs"][0]["logRecords"][0];
+        assert_eq!(record["timeUnixNano"], "1700000000123000000");
+        assert_eq!(record["severityNumber"], 17);
+        assert_eq!(record["body"]["stringValue"], "boom");
+        assert_eq!(record["traceId"], "abcd");
+
+        let logs = error_logs(&decoded);
+        assert_eq!(logs.len(), 1);
+        let log = &logs[0];
+        assert_eq!(log.package, "app");
+        assert_eq!(log.version, "1.2.0");
+        assert_eq!(log.message, "TypeError");
+        assert_eq!(
+            log.logs,
+            "boom\nscope: lib\ntraceId: abcd\nhttp.method: GET"
+        );
+        assert_eq!(log.time, Some(1_700_000_000));
+    }
+
+    /// 截断或长度超出范围的输入返回错误
+    #[test]
+    fn rejects_truncated_protobuf() {
+        let inputs = [
+            vec![0x0a],
+            vec![0x0a, 0x80],
+            vec![0x0a, 0x05, 0x01],
+            [tag(1, 2), varint(u64::MAX)].concat(),
+            [vec![0x08], vec![0xff; 10]].concat(),
+            [tag(1, 1), vec![1, 2, 3]].concat(),
+            [tag(1, 5), vec![1]].concat(),
+            bytes(1, &[0x0a, 0x09]),
+            [tag(7, 3), uint(1, 1)].concat(),
+            {
+                let mut input = request(&[error_record()]);
+                input.pop();
+                input
+            },
+        ];
+        for input in inputs {
+            assert!(
+                proto::decode_logs_request(&input).is_err(),
+                "{:02x?}",
+                input
+            );
+        }
+    }
+
+    /// 未知的字段、字段类型不符及 group 跳过, 无法确定长度的类型返回错误
+    #[test]
+    fn skips_unknown_protobuf_fields() {
+        let group = [
+            tag(104, 3),
+            uint(1, 1),
+            tag(105, 3),
+            bytes(2, b"x"),
+            tag(105, 4),
+            tag(104, 4),
+        ]
+        .concat();
+        let noisy_record = [
+            uint(100, 7),
+            error_record(),
+            fixed64(101, 1),
+            bytes(102, b"xyz"),
+            [tag(103, 5), vec![0; 4]].concat(),
+            group.clone(),
+            // 已知字段的类型不符
+            bytes(2, b"9"),
+            uint(5, 1),
+        ]
+        .concat();
+        let noisy = [
+            uint(15, 1),
+            group,
+            request(&[noisy_record]),
+            [tag(16, 5), vec![0; 4]].concat(),
+        ]
+        .concat();
+
+        assert_eq!(
+            proto::decode_logs_request(&noisy).unwrap(),
+            proto::decode_logs_request(&request(&[error_record()])).unwrap()
+        );
+
+        let inputs = [
+            [tag(1, 6), vec![0; 8]].concat(),
+            [tag(1, 7), vec![0; 8]].concat(),
+            tag(1, 4),
+            [tag(104, 3), tag(105, 4)].concat(),
+        ];
+        for input in inputs {
+            assert!(
+                proto::decode_logs_request(&input).is_err(),
+                "{:02x?}",
+                input
+            );
+        }
+    }
+
+    /// 交替嵌套数组及键值列表, 超过 `MAX_DEPTH` 时拒绝
+    #[test]
+    fn rejects_deeply_nested_values() {
+        let nested = |depth: usize| {
+            let mut value = bytes(1, b"leaf");
+            for level in 0..depth {
+                value = if level % 2 == 0 {
+                    bytes(5, &bytes(1, &value))
+                } else {
+                    bytes(6, &bytes(1, &[bytes(1, b"key"), bytes(2, &value)].concat()))
+                };
+            }
+            request(&[[uint(2, 17), bytes(5, &value)].concat()])
+        };
+
+        assert!(proto::decode_logs_request(&nested(proto::MAX_DEPTH)).is_ok());
+        assert!(proto::decode_logs_request(&nested(proto::MAX_DEPTH + 1)).is_err());
+        assert!(proto::decode_logs_request(&nested(1000)).is_err());
+    }
+
+    /// OTLP/JSON 使用 camelCase 字段名, 时间戳及枚举可能为字符串
+    #[test]
+    fn converts_json_request() {
+        let request = json!({
+            "resourceLogs": [{
+                "resource": {"attributes": [
+                    {"key": "service.name", "value": {"stringValue": "app"}},
+                    {"key": "service.version", "value": {"stringValue": "1.2.0"}},
+                    {"key": "host.name", "value": {"stringValue": "web-1"}},
+                ]},
+                "scopeLogs": [
+                    {
+                        "scope": {"name": "lib"},
+                        "logRecords": [
+                            {
+                                "timeUnixNano": "1700000000123000000",
+                                "severityNumber": "SEVERITY_NUMBER_ERROR",
+                                "body": {"stringValue": "request failed"},
+                                "attributes": [
+                                    {"key": "exception.type", "value": {"stringValue": "TypeError"}},
+                                    {"key": "exception.message", "value": {"stringValue": "x is undefined"}},
+                                    {"key": "exception.stacktrace", "value": {"stringValue": "TypeError: x is undefined\n    at f (app.js:1:2)"}},
+                                    {"key": "retry", "value": {"intValue": "3"}},
+                                ],
+                                "traceId": "abcd",
+                            },
+                            {
+                                "observedTimeUnixNano": 1_700_000_001_000_000_000u64,
+                                "severityText": "Error",
+                                "body": {"kvlistValue": {"values": [
+                                    {"key": "code", "value": {"intValue": "500"}},
+                                ]}},
+                            },
+                            {"severityNumber": 9, "severityText": "ERROR", "body": {"stringValue": "info"}},
+                        ],
+                    },
+                    // 非 camelCase 的字段名不解析
+                    {"scope": {"name": "other"}, "log_records": [{"severityNumber": 17}]},
+                ],
+            }],
+        });
+
+        let logs = error_logs(&request);
+        assert_eq!(logs.len(), 2);
+
+        assert_eq!(
+            logs[0].message,
+            "TypeError: x is undefined\n    at f (app.js:1:2)"
+        );
+        assert_eq!(logs[0].package, "app");
+        assert_eq!(logs[0].version, "1.2.0");
+        assert_eq!(logs[0].user, "web-1");
+        assert_eq!(
+            logs[0].logs,
+            "request failed\nscope: lib\ntraceId: abcd\nretry: 3\nhost.name: web-1"
+        );
+        assert_eq!(logs[0].time, Some(1_700_000_000));
+
+        assert_eq!(logs[1].message, "{code: 500}");
+        assert_eq!(logs[1].logs, "scope: lib\nhost.name: web-1");
+        assert_eq!(logs[1].time, Some(1_700_000_001));
+    }
+}
@@ -0,0 +1,297 @@
+use crate::api::{json_error, map_db_err, user_authentication, AppState};
+use crate::orm_entities::prelude::{Project, ProjectKey};
+use crate::orm_entities::{project, project_key};
+use actix_web::http::StatusCode;
+use actix_web::{post, web, HttpRequest, HttpResponse};
+use actix_web_httpauth::extractors::basic::BasicAuth;
+use chrono::Utc;
+use sea_orm::ActiveValue::Set;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, EntityTrait, NotSet, QueryFilter, QueryOrder,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// 上报密钥请求头
+const INGEST_KEY_HEADER: &str = "X-Ingest-Key";
+/// Sentry SDK 的鉴权请求头
+const SENTRY_AUTH_HEADER: &str = "X-Sentry-Auth";
+
+fn generate_key() -> String {
+    format!("{:032x}", rand::random::<u128>())
+}
+
+/// 从请求头或 `?key=` 参数中读取上报密钥
+pub fn ingest_key_from_request(req: &HttpRequest) -> Option<String> {
+    if let Some(key) = req
+        .headers()
+        .get(INGEST_KEY_HEADER)
+        .and_then(|x| x.to_str().ok())
+    {
+        return Some(key.trim().to_string());
+    }
+
+    // Sentry SDK 通过 `X-Sentry-Auth: Sentry sentry_key=...` 或 `?sentry_key=` 传递 DSN 中的密钥
+    if let Some(key) = req
+        .headers()
+        .get(SENTRY_AUTH_HEADER)
+        .and_then(|x| x.to_str().ok())
+        .and_then(|x| {
+            x.trim_start_matches("Sentry ")
+                .split(',')
+                .filter_map(|x| x.trim().split_once('='))
+                .find(|(name, _)| *name == "sentry_key")
+                .map(|(_, key)| key.trim().to_string())
+        })
+    {
+        return Some(key);
+    }
+
+    web::Query::<HashMap<String, String>>::from_query(req.query_string())
+        .ok()
+        .and_then(|query| query.get("key").or(query.get("sentry_key")).cloned())
+}
+
+/// 校验上报密钥, 返回密钥所属的项目id
+///
+/// 未携带密钥时, 若服务端未开启 `--require-ingest-key` 则返回 0(未指定项目)
+pub async fn authorize_ingest(
+    req: &HttpRequest,
+    app_data: &web::Data<AppState>,
+) -> actix_web::Result<i32> {
+    let Some(key) = ingest_key_from_request(req) else {
+        if app_data.require_ingest_key {
+            return Err(json_error(StatusCode::UNAUTHORIZED, "missing ingest key"));
+        }
+        return Ok(0);
+    };
+
+    let key_model = ProjectKey::find()
+        .filter(project_key::Column::PublicKey.eq(key))
+        .one(app_data.db_pool.get().unwrap())
+        .await
+        .map_err(map_db_err)?;
+
+    match key_model {
+        Some(key_model) if key_model.revoke_time.is_none() => Ok(key_model.project_id),
+        Some(_) => Err(json_error(StatusCode::FORBIDDEN, "ingest key revoked")),
+        None => Err(json_error(StatusCode::FORBIDDEN, "invalid ingest key")),
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct ProjectKeyItemData {
+    id: i32,
+    public_key: String,
+    dsn: String,
+    create_time: i64,
+    revoked: bool,
+}
+
+#[derive(Serialize, Debug)]
+struct ProjectItemData {
+    id: i32,
+    name: String,
+    create_time: i64,
+    keys: Vec<ProjectKeyItemData>,
+}
+
+fn project_key_item(req: &HttpRequest, key: project_key::Model) -> ProjectKeyItemData {
+    let conn = req.connection_info();
+    ProjectKeyItemData {
+        id: key.id,
+        dsn: format!(
+            "{}://{}@{}/{}",
+            conn.scheme(),
+            key.public_key,
+            conn.host(),
+            key.project_id
+        ),
+        public_key: key.public_key,
+        create_time: key.create_time.and_utc().timestamp(),
+        revoked: key.revoke_time.is_some(),
+    }
+}
+
+async fn insert_project_key<C: ConnectionTrait>(
+    db: &C,
+    project_id: i32,
+) -> Result<project_key::Model, sea_orm::DbErr> {
+    project_key::ActiveModel {
+        id: NotSet,
+        project_id: Set(project_id),
+        public_key: Set(generate_key()),
+        create_time: Set(Utc::now().naive_utc()),
+        revoke_time: Set(None),
+    }
+    .insert(db)
+    .await
+}
+
+#[post("/api/project_list")]
+pub async fn api_project_list(
+    req: HttpRequest,
+    credentials: BasicAuth,
+    app_data: web::Data<AppState>,
+) -> actix_web::Result<HttpResponse> {
+    if !user_authentication(&req, &credentials, &app_data)
+        .await?
+        .is_admin()
+    {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let projects = Project::find()
+        .order_by_asc(project::Column::Id)
+        .find_with_related(ProjectKey)
+        .all(app_data.db_pool.get().unwrap())
+        .await
+        .map_err(map_db_err)?;
+
+    let items: Vec<ProjectItemData> = projects
+        .into_iter()
+        .map(|(project, keys)| ProjectItemData {
+            id: project.id,
+            name: project.name,
+            create_time: project.create_time.and_utc().timestamp(),
+            keys: keys
+                .into_iter()
+                .map(|key| project_key_item(&req, key))
+                .collect(),
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(items))
+}
+
+#[derive(Deserialize, Debug)]
+struct ProjectCreateRequestData {
+    name: String,
+}
+
+#[post("/api/project_create")]
+pub async fn api_project_create(
+    req: HttpRequest,
+    credentials: BasicAuth,
+    app_data: web::Data<AppState>,
+    json_data: web::Json<ProjectCreateRequestData>,
+) -> actix_web::Result<HttpResponse> {
+    if !user_authentication(&req, &credentials, &app_data)
+        .await?
+        .is_admin()
+    {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let name = json_data.name.trim();
+    if name.is_empty() {
+        return Err(json_error(StatusCode::BAD_REQUEST, "project name is empty"));
+    }
+
+    let db = app_data.db_pool.get().unwrap();
+    let project = project::ActiveModel {
+        id: NotSet,
+        name: Set(name.to_string()),
+        create_time: Set(Utc::now().naive_utc()),
+    }
+    .insert(db)
+    .await
+    .map_err(map_db_err)?;
+    let key = insert_project_key(db, project.id)
+        .await
+        .map_err(map_db_err)?;
+
+    Ok(HttpResponse::Ok().json(ProjectItemData {
+        id: project.id,
+        name: project.name,
+        create_time: project.create_time.and_utc().timestamp(),
+        keys: vec![project_key_item(&req, key)],
+    }))
+}
+
+#[derive(Deserialize, Debug)]
+struct ProjectKeyRotateRequestData {
+    project_id: i32,
+    // 是否同时吊销该项目的旧密钥
+    #[serde(default)]
+    revoke_old: bool,
+}
+
+#[post("/api/project_key_rotate")]
+pub async fn api_project_key_rotate(
+    req: HttpRequest,
+    credentials: BasicAuth,
+    app_data: web::Data<AppState>,
+    json_data: web::Json<ProjectKeyRotateRequestData>,
+) -> actix_web::Result<HttpResponse> {
+    if !user_authentication(&req, &credentials, &app_data)
+        .await?
+        .is_admin()
+    {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let db = app_data.db_pool.get().unwrap();
+    if Project::find_by_id(json_data.project_id)
+        .one(db)
+        .await
+        .map_err(map_db_err)?
+        .is_none()
+    {
+        return Err(json_error(StatusCode::NOT_FOUND, "project not found"));
+    }
+
+    if json_data.revoke_old {
+        ProjectKey::update_many()
+            .col_expr(
+                project_key::Column::RevokeTime,
+                Utc::now().naive_utc().into(),
+            )
+            .filter(project_key::Column::ProjectId.eq(json_data.project_id))
+            .filter(project_key::Column::RevokeTime.is_null())
+            .exec(db)
+            .await
+            .map_err(map_db_err)?;
+    }
+
+    let key = insert_project_key(db, json_data.project_id)
+        .await
+        .map_err(map_db_err)?;
+
+    Ok(HttpResponse::Ok().json(project_key_item(&req, key)))
+}
+
+#[derive(Deserialize, Debug)]
+struct ProjectKeyRevokeRequestData {
+    id: i32,
+}
+
+#[post("/api/project_key_revoke")]
+pub async fn api_project_key_revoke(
+    req: HttpRequest,
+    credentials: BasicAuth,
+    app_data: web::Data<AppState>,
+    json_data: web::Json<ProjectKeyRevokeRequestData>,
+) -> actix_web::Result<HttpResponse> {
+    if !user_authentication(&req, &credentials, &app_data)
+        .await?
+        .is_admin()
+    {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let db = app_data.db_pool.get().unwrap();
+    if let Some(key) = ProjectKey::find_by_id(json_data.id)
+        .one(db)
+        .await
+        .map_err(map_db_err)?
+    {
+        if key.revoke_time.is_none() {
+            let mut key: project_key::ActiveModel = key.into();
+            key.revoke_time = Set(Some(Utc::now().naive_utc()));
+            key.update(db).await.map_err(map_db_err)?;
+        }
+    }
+
+    Ok(HttpResponse::Ok().body("{\"data\": \"ok\"}"))
+}
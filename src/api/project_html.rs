@@ -0,0 +1,20 @@
+use crate::api::{user_authentication, AppState};
+use actix_web::{get, web, HttpRequest, HttpResponse, Responder};
+use actix_web_httpauth::extractors::basic::BasicAuth;
+
+#[get("/projects")]
+pub async fn projects(
+    req: HttpRequest,
+    credentials: BasicAuth,
+    app_data: web::Data<AppState>,
+) -> actix_web::Result<impl Responder> {
+    if !user_authentication(&req, &credentials, &app_data)
+        .await?
+        .is_admin()
+    {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let html = include_str!("../html/projects.html");
+    Ok(HttpResponse::Ok().content_type("text/html").body(html))
+}
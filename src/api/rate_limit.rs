@@ -0,0 +1,477 @@
+use crate::api::project::ingest_key_from_request;
+use crate::api::{user_authentication, AppState};
+use actix_web::error::InternalError;
+use actix_web::http::header::RETRY_AFTER;
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use actix_web_httpauth::extractors::basic::BasicAuth;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Instant;
+
+// 令牌桶数量超过该值时清理已回满的桶
+const MAX_IDLE_BUCKETS: usize = 10000;
+// 统计中保留的被拒绝最多的来源数量
+const TOP_REJECTED: usize = 20;
+
+/// 令牌桶限流参数
+#[derive(Clone, Copy, Debug)]
+pub struct RateLimit {
+    /// 每分钟补充的令牌数, 0 表示不限流
+    pub per_minute: u32,
+    /// 桶容量, 即允许的突发请求数
+    pub burst: u32,
+}
+
+struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+#[derive(Default)]
+struct LimiterState {
+    buckets: HashMap<String, TokenBucket>,
+    rejected: HashMap<String, u64>,
+    rejected_total: u64,
+}
+
+/// 一类来源(IP / 上报密钥)的限流器
+struct Limiter {
+    limit: RateLimit,
+    state: Mutex<LimiterState>,
+}
+
+impl Limiter {
+    fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            state: Default::default(),
+        }
+    }
+
+    fn rate(&self) -> f64 {
+        self.limit.per_minute as f64 / 60.0
+    }
+
+    /// 补充令牌后返回还需等待的秒数, 0 表示可以通过
+    fn refill(&self, bucket: &mut TokenBucket, now: Instant) -> u64 {
+        let burst = self.limit.burst.max(1) as f64;
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.rate()).min(burst);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            0
+        } else {
+            ((1.0 - bucket.tokens) / self.rate()).ceil() as u64
+        }
+    }
+
+    /// 检查所有来源, 按每条一个令牌计算可以通过的条数(不超过 `cost`), 各来源消耗相应的令牌;
+    /// 一条也不能通过时返回需要等待的秒数
+    fn acquire(&self, sources: &[String], cost: usize, now: Instant) -> Result<usize, u64> {
+        if self.limit.per_minute == 0 || sources.is_empty() || cost == 0 {
+            return Ok(cost);
+        }
+
+        let mut state = self.state.lock().unwrap();
+        if state.buckets.len() > MAX_IDLE_BUCKETS {
+            let burst = self.limit.burst.max(1) as f64;
+            let rate = self.rate();
+            state.buckets.retain(|_, bucket| {
+                bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() * rate < burst
+            });
+        }
+
+        let mut granted = cost;
+        let mut retry_after = 0;
+        let mut limited = vec![];
+        for source in sources {
+            let bucket = state
+                .buckets
+                .entry(source.clone())
+                .or_insert_with(|| TokenBucket {
+                    tokens: self.limit.burst.max(1) as f64,
+                    updated: now,
+                });
+            retry_after = retry_after.max(self.refill(bucket, now));
+            let available = bucket.tokens.max(0.0).floor() as usize;
+            if available < cost {
+                limited.push(source);
+            }
+            granted = granted.min(available);
+        }
+
+        // 部分通过的请求同样计入被拒绝的统计
+        if granted < cost {
+            state.rejected_total += 1;
+            for source in limited {
+                *state.rejected.entry(source.clone()).or_default() += 1;
+            }
+            if state.rejected.len() > MAX_IDLE_BUCKETS {
+                let mut counts: Vec<u64> = state.rejected.values().copied().collect();
+                counts.sort_unstable_by(|a, b| b.cmp(a));
+                let min = counts[TOP_REJECTED];
+                state.rejected.retain(|_, rejected| *rejected > min);
+            }
+        }
+        if granted == 0 {
+            return Err(retry_after);
+        }
+        for source in sources {
+            if let Some(bucket) = state.buckets.get_mut(source) {
+                bucket.tokens -= granted as f64;
+            }
+        }
+        Ok(granted)
+    }
+
+    /// 归还已消耗但未使用的令牌
+    fn refund(&self, sources: &[String], count: usize) {
+        if self.limit.per_minute == 0 || count == 0 {
+            return;
+        }
+
+        let burst = self.limit.burst.max(1) as f64;
+        let mut state = self.state.lock().unwrap();
+        for source in sources {
+            if let Some(bucket) = state.buckets.get_mut(source) {
+                bucket.tokens = (bucket.tokens + count as f64).min(burst);
+            }
+        }
+    }
+
+    fn stats(&self) -> RateLimitStatsData {
+        let state = self.state.lock().unwrap();
+        let mut top: Vec<RejectedSourceData> = state
+            .rejected
+            .iter()
+            .map(|(source, rejected)| RejectedSourceData {
+                source: source.clone(),
+                rejected: *rejected,
+            })
+            .collect();
+        top.sort_by(|a, b| b.rejected.cmp(&a.rejected).then(a.source.cmp(&b.source)));
+        top.truncate(TOP_REJECTED);
+
+        RateLimitStatsData {
+            per_minute: self.limit.per_minute,
+            burst: self.limit.burst,
+            rejected_total: state.rejected_total,
+            top,
+        }
+    }
+}
+
+/// 上报接口的限流, 分别按客户端 IP 及上报密钥限流, 未携带密钥时按包名限流
+pub struct RateLimiter {
+    ip: Limiter,
+    key: Limiter,
+    /// 是否信任反向代理设置的 `Forwarded` / `X-Forwarded-For` 请求头
+    trust_proxy: bool,
+}
+
+impl RateLimiter {
+    pub fn new(ip: RateLimit, key: RateLimit, trust_proxy: bool) -> Self {
+        Self {
+            ip: Limiter::new(ip),
+            key: Limiter::new(key),
+            trust_proxy,
+        }
+    }
+
+    /// 按 IP 限流使用的客户端地址
+    ///
+    /// 转发请求头可以由客户端任意伪造, 只有部署在反向代理之后并开启 `--trust-proxy` 时才使用
+    fn client_ip(&self, req: &HttpRequest) -> String {
+        if self.trust_proxy {
+            if let Some(ip) = req.connection_info().realip_remote_addr() {
+                return ip.to_string();
+            }
+        }
+        req.peer_addr()
+            .map_or_else(|| "unknown".to_string(), |x| x.ip().to_string())
+    }
+
+    /// 检查一次上报请求, 每条上报消耗一个令牌, 返回允许写入的条数(不超过 `count`)
+    ///
+    /// 一条也不允许时返回带 `Retry-After` 的 429 错误.
+    /// `packages` 为请求中上报的包名, 仅在未携带上报密钥时使用
+    pub fn check<'a>(
+        &self,
+        req: &HttpRequest,
+        packages: impl IntoIterator<Item = &'a str>,
+        count: usize,
+    ) -> actix_web::Result<usize> {
+        self.acquire(req, packages, count, 0)
+    }
+
+    /// 请求内容解析之后按上报条数补充检查, 返回允许写入的条数(不超过 `count`)
+    ///
+    /// 用于需要先解析才能得到条数及包名的接口, 解析之前应先以空的包名调用 `check` 检查一条,
+    /// 携带上报密钥时第一条已在解析之前消耗过令牌; 未携带密钥时按包名限流
+    pub fn check_items<'a>(
+        &self,
+        req: &HttpRequest,
+        packages: impl IntoIterator<Item = &'a str>,
+        count: usize,
+    ) -> actix_web::Result<usize> {
+        self.acquire(req, packages, count, count.min(1))
+    }
+
+    /// 按条数消耗 IP 及 上报密钥 / 包名 的令牌, `paid` 为之前已消耗过令牌的条数
+    fn acquire<'a>(
+        &self,
+        req: &HttpRequest,
+        packages: impl IntoIterator<Item = &'a str>,
+        count: usize,
+        paid: usize,
+    ) -> actix_web::Result<usize> {
+        let now = Instant::now();
+        let ip = [self.client_ip(req)];
+
+        // 未携带密钥时之前未按包名消耗过令牌
+        let (mut sources, key_paid): (Vec<String>, usize) = match ingest_key_from_request(req) {
+            Some(key) => (vec![format!("key:{}", key)], paid),
+            None => (
+                packages
+                    .into_iter()
+                    .map(|x| format!("package:{}", x))
+                    .collect(),
+                0,
+            ),
+        };
+        sources.sort();
+        sources.dedup();
+
+        let ip_granted = match self.ip.acquire(&ip, count - paid, now) {
+            Ok(x) => x,
+            Err(_) if paid > 0 => 0,
+            Err(retry_after) => return Err(too_many_requests(retry_after)),
+        };
+        let allowed = paid + ip_granted;
+        let granted = match self.key.acquire(&sources, allowed - key_paid, now) {
+            Ok(x) => key_paid + x,
+            Err(_) if key_paid > 0 => key_paid,
+            Err(retry_after) => {
+                self.ip.refund(&ip, ip_granted);
+                return Err(too_many_requests(retry_after));
+            }
+        };
+        self.ip.refund(&ip, allowed - granted);
+        Ok(granted)
+    }
+}
+
+/// 带 `Retry-After` 的 429 错误
+fn too_many_requests(retry_after: u64) -> actix_web::Error {
+    InternalError::from_response(
+        "too many requests",
+        HttpResponse::TooManyRequests()
+            .insert_header((RETRY_AFTER, retry_after.to_string()))
+            .json(serde_json::json!({ "error": "too many requests" })),
+    )
+    .into()
+}
+
+#[derive(Serialize, Debug)]
+struct RejectedSourceData {
+    source: String,
+    rejected: u64,
+}
+
+#[derive(Serialize, Debug)]
+struct RateLimitStatsData {
+    per_minute: u32,
+    burst: u32,
+    // 被拒绝(含部分拒绝)的请求总数
+    rejected_total: u64,
+    // 被拒绝次数最多的来源
+    top: Vec<RejectedSourceData>,
+}
+
+#[derive(Serialize, Debug)]
+struct RateLimitStatsResponseData {
+    ip: RateLimitStatsData,
+    key: RateLimitStatsData,
+}
+
+/// 服务启动以来被限流拒绝的上报统计
+#[get("/api/rate_limit/stats")]
+pub async fn api_rate_limit_stats(
+    req: HttpRequest,
+    credentials: BasicAuth,
+    app_data: web::Data<AppState>,
+) -> actix_web::Result<HttpResponse> {
+    if !user_authentication(&req, &credentials, &app_data)
+        .await?
+        .is_admin()
+    {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    Ok(HttpResponse::Ok().json(RateLimitStatsResponseData {
+        ip: app_data.rate_limiter.ip.stats(),
+        key: app_data.rate_limiter.key.stats(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::StatusCode;
+    use actix_web::test::TestRequest;
+    use std::time::Duration;
+
+    fn limiter(per_minute: u32, burst: u32) -> Limiter {
+        Limiter::new(RateLimit { per_minute, burst })
+    }
+
+    fn sources(names: &[&str]) -> Vec<String> {
+        names.iter().map(|x| x.to_string()).collect()
+    }
+
+    fn tokens(limiter: &Limiter, source: &str) -> f64 {
+        limiter.state.lock().unwrap().buckets[source].tokens
+    }
+
+    /// 突发请求用完桶容量后拒绝, 按速率补充令牌
+    #[test]
+    fn bucket_allows_burst_then_refills() {
+        let limiter = limiter(60, 3);
+        let a = sources(&["a"]);
+        let now = Instant::now();
+
+        for _ in 0..3 {
+            assert_eq!(limiter.acquire(&a, 1, now), Ok(1));
+        }
+        assert_eq!(limiter.acquire(&a, 1, now), Err(1));
+        assert_eq!(limiter.acquire(&a, 1, now + Duration::from_secs(1)), Ok(1));
+
+        // 只有部分令牌时按可用数量通过
+        let later = now + Duration::from_millis(3500);
+        assert_eq!(limiter.acquire(&a, 5, later), Ok(2));
+        // 补充不超过桶容量
+        let idle = later + Duration::from_secs(600);
+        assert_eq!(limiter.acquire(&a, 5, idle), Ok(3));
+
+        let stats = limiter.stats();
+        assert_eq!(stats.rejected_total, 3);
+        assert_eq!(stats.top.len(), 1);
+        assert_eq!(stats.top[0].rejected, 3);
+    }
+
+    /// 等待时间按缺少的令牌及补充速率向上取整
+    #[test]
+    fn retry_after_follows_refill_rate() {
+        let limiter = limiter(30, 1);
+        let a = sources(&["a"]);
+        let now = Instant::now();
+
+        assert_eq!(limiter.acquire(&a, 1, now), Ok(1));
+        assert_eq!(limiter.acquire(&a, 1, now), Err(2));
+        assert_eq!(
+            limiter.acquire(&a, 1, now + Duration::from_millis(500)),
+            Err(2)
+        );
+        assert_eq!(limiter.acquire(&a, 1, now + Duration::from_secs(1)), Err(1));
+        assert_eq!(limiter.acquire(&a, 1, now + Duration::from_secs(2)), Ok(1));
+    }
+
+    /// 多个来源按可用令牌最少的来源通过, 各来源消耗相同数量的令牌
+    #[test]
+    fn every_source_is_charged() {
+        let limiter = limiter(60, 4);
+        let now = Instant::now();
+
+        assert_eq!(limiter.acquire(&sources(&["a"]), 3, now), Ok(3));
+        assert_eq!(limiter.acquire(&sources(&["a", "b"]), 3, now), Ok(1));
+        assert_eq!(tokens(&limiter, "a"), 0.0);
+        assert_eq!(tokens(&limiter, "b"), 3.0);
+
+        let stats = limiter.stats();
+        assert_eq!(stats.rejected_total, 1);
+        assert_eq!(stats.top.len(), 1);
+        assert_eq!(stats.top[0].source, "a");
+
+        limiter.refund(&sources(&["a", "b"]), 2);
+        assert_eq!(tokens(&limiter, "a"), 2.0);
+        assert_eq!(tokens(&limiter, "b"), 4.0);
+    }
+
+    /// 不限流、没有来源或不消耗令牌时直接通过
+    #[test]
+    fn unlimited_requests_pass() {
+        let now = Instant::now();
+        assert_eq!(limiter(0, 0).acquire(&sources(&["a"]), 1000, now), Ok(1000));
+        assert_eq!(limiter(60, 1).acquire(&[], 1000, now), Ok(1000));
+        assert_eq!(limiter(60, 1).acquire(&sources(&["a"]), 0, now), Ok(0));
+
+        // 桶容量为 0 时按 1 计算
+        let limiter = limiter(60, 0);
+        assert_eq!(limiter.acquire(&sources(&["a"]), 2, now), Ok(1));
+        assert_eq!(limiter.acquire(&sources(&["a"]), 1, now), Err(1));
+    }
+
+    /// 同一上报密钥在不同 IP 之间共享令牌, 被密钥拒绝时归还 IP 的令牌
+    #[test]
+    fn key_bucket_is_shared_across_ips() {
+        let limiter = RateLimiter::new(
+            RateLimit {
+                per_minute: 60,
+                burst: 10,
+            },
+            RateLimit {
+                per_minute: 60,
+                burst: 3,
+            },
+            false,
+        );
+        let request = |ip: &str| {
+            TestRequest::default()
+                .peer_addr(format!("{}:1234", ip).parse().unwrap())
+                .insert_header(("X-Ingest-Key", "secret"))
+                .to_http_request()
+        };
+
+        assert_eq!(limiter.check(&request("10.0.0.1"), [], 1).unwrap(), 1);
+        // 解析之后补充检查, 第一条已经消耗过令牌
+        assert_eq!(
+            limiter
+                .check_items(&request("10.0.0.1"), ["app"], 5)
+                .unwrap(),
+            3
+        );
+
+        let err = limiter.check(&request("10.0.0.2"), [], 1).unwrap_err();
+        let response = err.error_response();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers().get(RETRY_AFTER).unwrap(), "1");
+        assert!(tokens(&limiter.ip, "10.0.0.2") >= 10.0 - 1e-6);
+        assert!(tokens(&limiter.ip, "10.0.0.1") < 8.0);
+    }
+
+    /// 未携带上报密钥时按包名分别限流
+    #[test]
+    fn packages_are_limited_without_key() {
+        let limiter = RateLimiter::new(
+            RateLimit {
+                per_minute: 0,
+                burst: 0,
+            },
+            RateLimit {
+                per_minute: 60,
+                burst: 2,
+            },
+            false,
+        );
+        let request = TestRequest::default()
+            .peer_addr("10.0.0.1:1234".parse().unwrap())
+            .to_http_request();
+
+        assert_eq!(limiter.check(&request, ["app", "app"], 2).unwrap(), 2);
+        assert!(limiter.check(&request, ["app"], 1).is_err());
+        assert_eq!(limiter.check(&request, ["other"], 1).unwrap(), 1);
+        // 同一请求包含多个包名时每个包名都需要令牌
+        assert_eq!(limiter.check(&request, ["other", "third"], 2).unwrap(), 1);
+    }
+}
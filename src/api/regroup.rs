@@ -0,0 +1,225 @@
+use crate::api::fingerprint::Fingerprinter;
+use crate::api::issue_status::record_package_versions;
+use crate::api::version::VersionSchemes;
+use crate::api::{map_db_err, user_authentication, AppState};
+use crate::orm_entities::prelude::{LogPackageVersion, UploadLog, UploadOccurrence};
+use crate::orm_entities::sea_orm_active_enums::IssueStatus;
+use crate::orm_entities::{log_package_version, upload_log, upload_occurrence};
+use actix_web::{post, web, HttpRequest, HttpResponse};
+use actix_web_httpauth::extractors::basic::BasicAuth;
+use sea_orm::sea_query::Expr;
+use sea_orm::ActiveValue::Set;
+use sea_orm::{
+    ColumnTrait, ConnectionTrait, DbErr, EntityTrait, NotSet, PaginatorTrait, QueryFilter,
+    QueryOrder, TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// 重新分组后的一组错误
+#[derive(Serialize, Debug)]
+pub struct RegroupChange {
+    /// 保留的错误id
+    pub log_id: i32,
+    pub old_hash: String,
+    pub new_hash: String,
+    /// 合并到该错误并被删除的错误id
+    pub merged_ids: Vec<i32>,
+    pub total_count: i32,
+}
+
+#[derive(Serialize, Debug)]
+pub struct RegroupReport {
+    pub dry_run: bool,
+    /// 检查的错误数量
+    pub scanned: u64,
+    pub changes: Vec<RegroupChange>,
+}
+
+/// 按当前的归一化规则及指纹计算流程重新计算所有错误的哈希值, 合并哈希相同的错误
+///
+/// 合并时保留最早出现的错误, 上报次数相加, 上报详情合并, 时间取最早的首次时间及最晚的最后时间;
+/// 手动合并到其他错误的错误只更新哈希值
+pub async fn regroup<C: ConnectionTrait + TransactionTrait>(
+    db: &C,
+    fingerprinter: &Fingerprinter,
+    versions: &VersionSchemes,
+    dry_run: bool,
+) -> Result<RegroupReport, DbErr> {
+    let mut groups: HashMap<String, Vec<upload_log::Model>> = HashMap::new();
+    let mut scanned = 0;
+
+    let mut pages = UploadLog::find()
+        .order_by_asc(upload_log::Column::Id)
+        .paginate(db, 1000);
+    while let Some(logs) = pages.fetch_and_next().await? {
+        for log in logs {
+            scanned += 1;
+            let hash = fingerprinter
+                .fingerprint(
+                    log.project_id,
+                    &log.log_type,
+                    &log.message,
+                    log.fingerprint.as_deref(),
+                )
+                .hash;
+            groups.entry(hash).or_default().push(log);
+        }
+    }
+
+    let mut changes = vec![];
+    for (new_hash, logs) in groups {
+        // 手动合并的错误只更新哈希值, 不参与自动合并
+        let (mut logs, merged): (Vec<_>, Vec<_>) =
+            logs.into_iter().partition(|x| x.merged_into.is_none());
+        for log in merged.into_iter().filter(|x| x.hash != new_hash) {
+            changes.push(RegroupChange {
+                log_id: log.id,
+                old_hash: log.hash,
+                new_hash: new_hash.clone(),
+                merged_ids: vec![],
+                total_count: log.total_count,
+            });
+        }
+
+        if logs.is_empty() || (logs.len() == 1 && logs[0].hash == new_hash) {
+            continue;
+        }
+
+        logs.sort_by_key(|x| (x.first_time, x.id));
+        let primary = &logs[0];
+        changes.push(RegroupChange {
+            log_id: primary.id,
+            old_hash: primary.hash.clone(),
+            new_hash,
+            merged_ids: logs[1..].iter().map(|x| x.id).collect(),
+            total_count: logs.iter().map(|x| x.total_count).sum(),
+        });
+    }
+    changes.sort_by_key(|x| x.log_id);
+
+    if !dry_run && !changes.is_empty() {
+        let txn = db.begin().await?;
+        for change in &mut changes {
+            // 扫描之后可能有新的上报或错误被删除, 以事务中重新读取的数据为准
+            let logs = UploadLog::find()
+                .filter(
+                    upload_log::Column::Id
+                        .is_in(change.merged_ids.iter().copied().chain([change.log_id])),
+                )
+                .all(&txn)
+                .await?;
+            let Some(primary) = logs.iter().find(|x| x.id == change.log_id) else {
+                continue;
+            };
+            change.total_count = logs.iter().map(|x| x.total_count).sum();
+
+            UploadOccurrence::update_many()
+                .col_expr(
+                    upload_occurrence::Column::Hash,
+                    Expr::value(change.new_hash.clone()),
+                )
+                .filter(upload_occurrence::Column::Hash.is_in(logs.iter().map(|x| x.hash.clone())))
+                .exec(&txn)
+                .await?;
+
+            if !change.merged_ids.is_empty() {
+                // 被删除的错误已上报的版本记录到保留的错误
+                let merged_versions = LogPackageVersion::find()
+                    .filter(log_package_version::Column::LogId.is_in(change.merged_ids.clone()))
+                    .all(&txn)
+                    .await?;
+                record_package_versions(
+                    &txn,
+                    versions,
+                    change.log_id,
+                    merged_versions
+                        .iter()
+                        .map(|x| (x.package.as_str(), x.latest_version.as_str())),
+                )
+                .await?;
+                UploadOccurrence::update_many()
+                    .col_expr(upload_occurrence::Column::LogId, Expr::value(change.log_id))
+                    .filter(upload_occurrence::Column::LogId.is_in(change.merged_ids.clone()))
+                    .exec(&txn)
+                    .await?;
+                UploadLog::update_many()
+                    .col_expr(upload_log::Column::MergedInto, Expr::value(change.log_id))
+                    .filter(upload_log::Column::MergedInto.is_in(change.merged_ids.clone()))
+                    .exec(&txn)
+                    .await?;
+                UploadLog::delete_many()
+                    .filter(upload_log::Column::Id.is_in(change.merged_ids.clone()))
+                    .exec(&txn)
+                    .await?;
+            }
+
+            UploadLog::update(upload_log::ActiveModel {
+                id: Set(change.log_id),
+                hash: Set(change.new_hash.clone()),
+                total_count: Set(change.total_count),
+                first_time: Set(logs
+                    .iter()
+                    .map(|x| x.first_time)
+                    .min()
+                    .unwrap_or(primary.first_time)),
+                last_time: Set(logs
+                    .iter()
+                    .map(|x| x.last_time)
+                    .max()
+                    .unwrap_or(primary.last_time)),
+                status: Set(IssueStatus::merge(logs.iter().map(|x| x.status))),
+                user_list: NotSet,
+                resolution_time: NotSet,
+                log_type: NotSet,
+                message: NotSet,
+                project_id: NotSet,
+                fingerprint: NotSet,
+                merged_into: NotSet,
+                muted_until: NotSet,
+                muted_until_count: NotSet,
+            })
+            .exec(&txn)
+            .await?;
+        }
+        txn.commit().await?;
+    }
+
+    Ok(RegroupReport {
+        dry_run,
+        scanned,
+        changes,
+    })
+}
+
+#[derive(Deserialize, Debug)]
+struct RegroupRequestData {
+    // 只返回将要发生的变更, 不修改数据
+    dry_run: bool,
+}
+
+#[post("/api/regroup")]
+pub async fn api_regroup(
+    req: HttpRequest,
+    credentials: BasicAuth,
+    app_data: web::Data<AppState>,
+    json_data: web::Json<RegroupRequestData>,
+) -> actix_web::Result<HttpResponse> {
+    if !user_authentication(&req, &credentials, &app_data)
+        .await?
+        .is_admin()
+    {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let report = regroup(
+        app_data.db_pool.get().unwrap(),
+        &app_data.fingerprinter,
+        &app_data.version_schemes,
+        json_data.dry_run,
+    )
+    .await
+    .map_err(map_db_err)?;
+
+    Ok(HttpResponse::Ok().json(report))
+}
@@ -0,0 +1,373 @@
+use crate::api::log::PendingLog;
+use crate::api::{map_db_err, user_authentication, AppState};
+use crate::orm_entities::prelude::{PackageRelease, PackageReleaseUser};
+use crate::orm_entities::{package_release, package_release_user, upload_statistics_cli_cfg};
+use actix_web::{post, web, HttpRequest, HttpResponse};
+use actix_web_httpauth::extractors::basic::BasicAuth;
+use chrono::NaiveDateTime;
+use sea_orm::sea_query::{Expr, OnConflict};
+use sea_orm::ActiveValue::Set;
+use sea_orm::{
+    ColumnTrait, Condition, ConnectionTrait, DbErr, EntityTrait, FromQueryResult, NotSet,
+    PaginatorTrait, QueryFilter, QueryOrder, QuerySelect,
+};
+use serde::{Deserialize, Serialize};
+
+/// (项目id, 包名, 版本)
+type ReleaseKey<'a> = (i32, &'a str, &'a str);
+
+/// 一批上报对版本统计的增量
+#[derive(Default)]
+struct ReleaseCounts {
+    launches: i32,
+    occurrences: i32,
+    new_issues: i32,
+    regressed_issues: i32,
+}
+
+/// 登记版本并累加统计, 返回版本id
+async fn upsert_release<C: ConnectionTrait>(
+    db: &C,
+    project_id: i32,
+    package: &str,
+    version: &str,
+    first_seen: NaiveDateTime,
+    last_seen: NaiveDateTime,
+    counts: ReleaseCounts,
+) -> Result<i32, DbErr> {
+    PackageRelease::insert(package_release::ActiveModel {
+        id: NotSet,
+        project_id: Set(project_id),
+        package: Set(package.to_string()),
+        version: Set(version.to_string()),
+        first_seen: Set(first_seen),
+        last_seen: Set(last_seen),
+        launch_count: Set(counts.launches),
+        occurrence_count: Set(counts.occurrences),
+        new_issue_count: Set(counts.new_issues),
+        regressed_issue_count: Set(counts.regressed_issues),
+    })
+    .on_conflict(
+        OnConflict::columns([
+            package_release::Column::ProjectId,
+            package_release::Column::Package,
+            package_release::Column::Version,
+        ])
+        // 离线缓存的上报可能早于已记录的时间
+        .value(
+            package_release::Column::FirstSeen,
+            Expr::cust("MIN(first_seen, excluded.first_seen)"),
+        )
+        .value(
+            package_release::Column::LastSeen,
+            Expr::cust("MAX(last_seen, excluded.last_seen)"),
+        )
+        .value(
+            package_release::Column::LaunchCount,
+            Expr::col(package_release::Column::LaunchCount).add(counts.launches),
+        )
+        .value(
+            package_release::Column::OccurrenceCount,
+            Expr::col(package_release::Column::OccurrenceCount).add(counts.occurrences),
+        )
+        .value(
+            package_release::Column::NewIssueCount,
+            Expr::col(package_release::Column::NewIssueCount).add(counts.new_issues),
+        )
+        .value(
+            package_release::Column::RegressedIssueCount,
+            Expr::col(package_release::Column::RegressedIssueCount).add(counts.regressed_issues),
+        )
+        .to_owned(),
+    )
+    .exec_without_returning(db)
+    .await?;
+
+    let release = PackageRelease::find()
+        .filter(package_release::Column::ProjectId.eq(project_id))
+        .filter(package_release::Column::Package.eq(package))
+        .filter(package_release::Column::Version.eq(version))
+        .one(db)
+        .await?
+        .ok_or(DbErr::RecordNotFound("package_release".into()))?;
+    Ok(release.id)
+}
+
+/// 记录用户使用过该版本, 空用户不参与统计
+async fn upsert_release_user<C: ConnectionTrait>(
+    db: &C,
+    release_id: i32,
+    user: &str,
+    launched: bool,
+    errored: bool,
+) -> Result<(), DbErr> {
+    if user.is_empty() {
+        return Ok(());
+    }
+
+    PackageReleaseUser::insert(package_release_user::ActiveModel {
+        id: NotSet,
+        release_id: Set(release_id),
+        user: Set(user.to_string()),
+        launched: Set(launched),
+        errored: Set(errored),
+    })
+    .on_conflict(
+        OnConflict::columns([
+            package_release_user::Column::ReleaseId,
+            package_release_user::Column::User,
+        ])
+        .value(
+            package_release_user::Column::Launched,
+            Expr::cust("MAX(launched, excluded.launched)"),
+        )
+        .value(
+            package_release_user::Column::Errored,
+            Expr::cust("MAX(errored, excluded.errored)"),
+        )
+        .to_owned(),
+    )
+    .exec_without_returning(db)
+    .await?;
+    Ok(())
+}
+
+/// 记录一次客户端启动到对应版本, 未上报版本时忽略
+pub async fn record_launch<C: ConnectionTrait>(
+    db: &C,
+    data: &upload_statistics_cli_cfg::Model,
+) -> Result<(), DbErr> {
+    if data.version.is_empty() {
+        return Ok(());
+    }
+
+    let release_id = upsert_release(
+        db,
+        data.project_id,
+        &data.package,
+        &data.version,
+        data.time,
+        data.time,
+        ReleaseCounts {
+            launches: 1,
+            ..Default::default()
+        },
+    )
+    .await?;
+    upsert_release_user(db, release_id, &data.user, true, false).await
+}
+
+/// 登记原始事件上报的版本, 原始事件不计入错误统计
+pub async fn record_event<C: ConnectionTrait>(db: &C, log: &PendingLog) -> Result<(), DbErr> {
+    let version = log.data.version.trim();
+    if version.is_empty() {
+        return Ok(());
+    }
+
+    upsert_release(
+        db,
+        log.project_id,
+        &log.data.package,
+        version,
+        log.time.time,
+        log.time.time,
+        ReleaseCounts::default(),
+    )
+    .await?;
+    Ok(())
+}
+
+/// 按版本记录同一错误的一批上报
+///
+/// `new_issue` 为错误是否由本批上报新建, 计入第一条上报的版本;
+/// `regressed` 为触发复现的上报的 (包名, 版本)
+pub async fn record_issue<C: ConnectionTrait>(
+    db: &C,
+    logs: &[&PendingLog],
+    new_issue: bool,
+    regressed: Option<(&str, &str)>,
+) -> Result<(), DbErr> {
+    let mut releases: Vec<(ReleaseKey, Vec<&PendingLog>)> = vec![];
+    for log in logs {
+        let version = log.data.version.trim();
+        if version.is_empty() {
+            continue;
+        }
+        let key = (log.project_id, log.data.package.as_str(), version);
+        match releases.iter_mut().find(|(x, _)| *x == key) {
+            Some((_, group)) => group.push(log),
+            None => releases.push((key, vec![log])),
+        }
+    }
+
+    let first = logs
+        .first()
+        .map(|x| (x.data.package.as_str(), x.data.version.trim()));
+    let regressed = regressed.map(|(package, version)| (package, version.trim()));
+    for ((project_id, package, version), group) in releases {
+        let release_id = upsert_release(
+            db,
+            project_id,
+            package,
+            version,
+            group.iter().map(|x| x.time.time).min().unwrap(),
+            group.iter().map(|x| x.time.time).max().unwrap(),
+            ReleaseCounts {
+                launches: 0,
+                occurrences: group.len() as i32,
+                new_issues: (new_issue && first == Some((package, version))) as i32,
+                regressed_issues: (regressed == Some((package, version))) as i32,
+            },
+        )
+        .await?;
+
+        let mut users: Vec<&str> = group.iter().map(|x| x.data.user.as_str()).collect();
+        users.sort_unstable();
+        users.dedup();
+        for user in users {
+            upsert_release_user(db, release_id, user, false, true).await?;
+        }
+    }
+    Ok(())
+}
+
+#[derive(Deserialize, Debug)]
+struct ReleaseListRequestData {
+    page: i32,
+    page_size: i32,
+    // 项目id, 为空时不过滤
+    #[serde(default)]
+    project_id: Option<i32>,
+    // 包名, 为空时不过滤
+    #[serde(default)]
+    package: String,
+}
+
+#[derive(FromQueryResult, Debug)]
+struct ReleaseRow {
+    id: i32,
+    project_id: i32,
+    package: String,
+    version: String,
+    first_seen: NaiveDateTime,
+    last_seen: NaiveDateTime,
+    launch_count: i32,
+    occurrence_count: i32,
+    new_issue_count: i32,
+    regressed_issue_count: i32,
+    users: i64,
+    crashed_users: i64,
+}
+
+#[derive(Serialize, Debug)]
+struct ReleaseItemData {
+    id: i32,
+    project_id: i32,
+    package: String,
+    version: String,
+    first_seen: i64,
+    last_seen: i64,
+    launch_count: i32,
+    occurrence_count: i32,
+    new_issue_count: i32,
+    regressed_issue_count: i32,
+    // 启动过或上报过错误的用户数
+    users: i64,
+    // 上报过错误的用户数
+    crashed_users: i64,
+    // 未上报错误的用户占比(百分比), 没有用户时为空
+    crash_free_percent: Option<f64>,
+}
+
+#[derive(Serialize, Debug)]
+struct ReleaseListResponseData {
+    success: bool,
+    total: i32,
+    total_pages: i32,
+    items: Vec<ReleaseItemData>,
+}
+
+/// 版本列表及各版本的错误统计, 按首次出现时间倒序
+#[post("/api/release_list")]
+pub async fn api_release_list(
+    req: HttpRequest,
+    credentials: BasicAuth,
+    app_data: web::Data<AppState>,
+    json_data: web::Json<ReleaseListRequestData>,
+) -> actix_web::Result<HttpResponse> {
+    let user = user_authentication(&req, &credentials, &app_data).await?;
+
+    let mut condition = Condition::all();
+    if let Some(project_id) = json_data.project_id {
+        condition = condition.add(package_release::Column::ProjectId.eq(project_id));
+    }
+    if !json_data.package.is_empty() {
+        condition = condition.add(package_release::Column::Package.eq(&json_data.package));
+    }
+    // 只能查看有权限的项目
+    if let Some(projects) = &user.projects {
+        let mut projects = projects.clone();
+        projects.push(0);
+        condition = condition.add(package_release::Column::ProjectId.is_in(projects));
+    }
+
+    let page = json_data.page.max(1);
+    let page_size = json_data.page_size.clamp(1, 100);
+
+    let db = app_data.db_pool.get().unwrap();
+    let total = PackageRelease::find()
+        .filter(condition.clone())
+        .count(db)
+        .await
+        .map_err(map_db_err)? as i32;
+
+    let items = PackageRelease::find()
+        .column_as(
+            Expr::cust(
+                "(SELECT COUNT(*) FROM package_release_user
+                WHERE package_release_user.release_id = package_release.id)",
+            ),
+            "users",
+        )
+        .column_as(
+            Expr::cust(
+                "(SELECT COUNT(*) FROM package_release_user
+                WHERE package_release_user.release_id = package_release.id
+                AND package_release_user.errored)",
+            ),
+            "crashed_users",
+        )
+        .filter(condition)
+        .order_by_desc(package_release::Column::FirstSeen)
+        .offset(((page - 1) * page_size) as u64)
+        .limit(page_size as u64)
+        .into_model::<ReleaseRow>()
+        .all(db)
+        .await
+        .map_err(map_db_err)?
+        .into_iter()
+        .map(|x| ReleaseItemData {
+            id: x.id,
+            project_id: x.project_id,
+            package: x.package,
+            version: x.version,
+            first_seen: x.first_seen.and_utc().timestamp(),
+            last_seen: x.last_seen.and_utc().timestamp(),
+            launch_count: x.launch_count,
+            occurrence_count: x.occurrence_count,
+            new_issue_count: x.new_issue_count,
+            regressed_issue_count: x.regressed_issue_count,
+            users: x.users,
+            crashed_users: x.crashed_users,
+            crash_free_percent: (x.users > 0)
+                .then(|| (x.users - x.crashed_users) as f64 / x.users as f64 * 100.0),
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(ReleaseListResponseData {
+        success: true,
+        total,
+        total_pages: (total + page_size - 1) / page_size,
+        items,
+    }))
+}
@@ -0,0 +1,329 @@
+use crate::api::fingerprint::{frame_key, Frame, MAX_FINGERPRINT_FRAMES};
+use crate::api::json_error;
+use crate::api::log::{client_ip, submit_log, UploadLogData};
+use crate::api::project::{authorize_ingest, ingest_key_from_request};
+use crate::api::AppState;
+use actix_web::http::header::ACCESS_CONTROL_ALLOW_ORIGIN;
+use actix_web::http::StatusCode;
+use actix_web::{post, web, HttpRequest, HttpResponse};
+use chrono::{DateTime, NaiveDateTime};
+use serde_json::Value;
+
+/// Sentry 事件中的字符串字段, 不存在或不是字符串时为空
+fn str_field<'a>(value: &'a Value, pointer: &str) -> &'a str {
+    value.pointer(pointer).and_then(Value::as_str).unwrap_or("")
+}
+
+/// Sentry 中可能是 `{"values": [...]}` 也可能直接是数组的字段
+fn values(value: Option<&Value>) -> &[Value] {
+    match value {
+        Some(Value::Array(x)) => x,
+        Some(x) => x
+            .get("values")
+            .and_then(Value::as_array)
+            .map_or(&[], |x| x.as_slice()),
+        None => &[],
+    }
+}
+
+/// 事件时间, 支持 unix 时间戳及 RFC 3339 格式
+fn event_time(event: &Value) -> Option<i64> {
+    match event.get("timestamp")? {
+        Value::Number(x) => x.as_f64().map(|x| x as i64),
+        Value::String(x) => DateTime::parse_from_rfc3339(x)
+            .map(|x| x.timestamp())
+            .or_else(|_| {
+                NaiveDateTime::parse_from_str(x, "%Y-%m-%dT%H:%M:%S%.f")
+                    .map(|x| x.and_utc().timestamp())
+            })
+            .ok(),
+        _ => None,
+    }
+}
+
+/// 栈帧, Sentry 中按调用顺序排列, 最后一帧为出错位置
+fn frames(exception: &Value) -> Vec<(Frame, i64)> {
+    values(exception.pointer("/stacktrace/frames"))
+        .iter()
+        .rev()
+        .map(|frame| {
+            let file = [
+                str_field(frame, "/filename"),
+                str_field(frame, "/abs_path"),
+                str_field(frame, "/module"),
+            ]
+            .into_iter()
+            .find(|x| !x.is_empty())
+            .unwrap_or_default();
+            (
+                Frame {
+                    function: str_field(frame, "/function").to_string(),
+                    file: file.to_string(),
+                    in_app: frame.get("in_app").and_then(Value::as_bool) == Some(true),
+                },
+                frame.get("lineno").and_then(Value::as_i64).unwrap_or_default(),
+            )
+        })
+        .collect()
+}
+
+/// 将 Sentry 事件转换为上报数据
+///
+/// 异常类型及应用内栈帧作为分组指纹, 事件中指定的 `fingerprint` 优先
+fn upload_log_data(event: &Value) -> UploadLogData {
+    let exceptions = values(event.get("exception"));
+    let mut message = vec![];
+    let mut default_fingerprint = None;
+
+    // 链式异常按发生顺序排列, 最后一个为最终抛出的异常
+    for (index, exception) in exceptions.iter().enumerate().rev() {
+        let kind = str_field(exception, "/type");
+        let value = str_field(exception, "/value");
+        message.push(match (kind.is_empty(), value.is_empty()) {
+            (false, false) => format!("{}: {}", kind, value),
+            (false, true) => kind.to_string(),
+            _ => value.to_string(),
+        });
+
+        let frames = frames(exception);
+        for (frame, lineno) in &frames {
+            message.push(format!("    at {} ({}:{})", frame.function, frame.file, lineno));
+        }
+
+        if index + 1 == exceptions.len() && !frames.is_empty() {
+            let in_app: Vec<_> = frames.iter().filter(|(x, _)| x.in_app).collect();
+            let frames = if in_app.is_empty() {
+                frames.iter().collect()
+            } else {
+                in_app
+            };
+            let mut components = vec![kind.to_string()];
+            components.extend(
+                frames
+                    .into_iter()
+                    .take(MAX_FINGERPRINT_FRAMES)
+                    .map(|(x, _)| frame_key(x)),
+            );
+            default_fingerprint = Some(format!("sentry:{}", components.join("\n")));
+        }
+    }
+    if message.is_empty() {
+        let text = [
+            str_field(event, "/logentry/formatted"),
+            str_field(event, "/logentry/message"),
+            str_field(event, "/message/formatted"),
+            str_field(event, "/message/message"),
+            str_field(event, "/message"),
+        ]
+        .into_iter()
+        .find(|x| !x.is_empty())
+        .unwrap_or_default();
+        message.push(text.to_string());
+    }
+
+    let fingerprint = match event.get("fingerprint").and_then(Value::as_array) {
+        Some(parts) if !parts.is_empty() => {
+            let parts: Vec<String> = parts
+                .iter()
+                .map(|x| match x.as_str() {
+                    Some("{{ default }}" | "{{default}}") => default_fingerprint
+                        .clone()
+                        .unwrap_or_else(|| message.join("\n")),
+                    Some(x) => x.to_string(),
+                    None => x.to_string(),
+                })
+                .collect();
+            Some(parts.join("\n"))
+        }
+        _ => default_fingerprint,
+    };
+
+    // release 格式为 `包名@版本`
+    let release = str_field(event, "/release");
+    let (package, version) = match release.split_once('@') {
+        Some((package, version)) => (package, version),
+        None => (release, str_field(event, "/contexts/app/app_version")),
+    };
+    let package = [package, str_field(event, "/contexts/app/app_identifier")]
+        .into_iter()
+        .find(|x| !x.is_empty())
+        .unwrap_or_else(|| str_field(event, "/platform"));
+
+    let user = [
+        str_field(event, "/user/id"),
+        str_field(event, "/user/username"),
+        str_field(event, "/user/email"),
+        str_field(event, "/user/ip_address"),
+    ]
+    .into_iter()
+    .find(|x| !x.is_empty())
+    .unwrap_or_default();
+
+    let mut logs: Vec<String> = values(event.get("breadcrumbs"))
+        .iter()
+        .map(|x| {
+            format!(
+                "[{}] {} {}",
+                x.get("timestamp").map(|x| x.to_string()).unwrap_or_default(),
+                str_field(x, "/category"),
+                str_field(x, "/message")
+            )
+        })
+        .collect();
+    for name in ["tags", "extra", "contexts"] {
+        if let Some(value) = event.get(name) {
+            logs.push(format!("{}: {}", name, value));
+        }
+    }
+
+    let log_type = match str_field(event, "/level") {
+        "" | "fatal" | "error" => "error",
+        level => level,
+    };
+
+    UploadLogData {
+        log_type: log_type.to_string(),
+        message: message.join("\n"),
+        user: user.to_string(),
+        package: package.to_string(),
+        nav_url: str_field(event, "/request/url").to_string(),
+        version: version.to_string(),
+        logs: logs.join("\n"),
+        fingerprint,
+        time: event_time(event),
+    }
+}
+
+/// 解析 envelope, 返回信封头及其中的事件
+///
+/// 格式为换行分隔的 JSON: 信封头, 之后每个条目为 条目头 + 内容, 条目头中指定 length 时按长度读取内容
+fn parse_envelope(body: &[u8]) -> Result<(Value, Vec<Value>), String> {
+    fn next_line<'a>(body: &'a [u8], pos: &mut usize) -> &'a [u8] {
+        let rest = &body[*pos..];
+        let end = rest.iter().position(|x| *x == b'\n').unwrap_or(rest.len());
+        *pos += (end + 1).min(rest.len());
+        &rest[..end]
+    }
+
+    let mut pos = 0;
+    let header: Value = serde_json::from_slice(next_line(body, &mut pos))
+        .map_err(|err| format!("invalid envelope header: {}", err))?;
+
+    let mut events = vec![];
+    while pos < body.len() {
+        let line = next_line(body, &mut pos);
+        if line.iter().all(u8::is_ascii_whitespace) {
+            continue;
+        }
+        let item_header: Value = serde_json::from_slice(line)
+            .map_err(|err| format!("invalid item header: {}", err))?;
+
+        let payload = match item_header.get("length").and_then(Value::as_u64) {
+            Some(length) => {
+                let end = usize::try_from(length)
+                    .ok()
+                    .and_then(|x| pos.checked_add(x))
+                    .filter(|x| *x <= body.len())
+                    .ok_or_else(|| "item length exceeds envelope".to_string())?;
+                let payload = &body[pos..end];
+                pos = end;
+                if body.get(pos) == Some(&b'\n') {
+                    pos += 1;
+                }
+                payload
+            }
+            None => next_line(body, &mut pos),
+        };
+
+        if str_field(&item_header, "/type") == "event" {
+            events.push(
+                serde_json::from_slice(payload)
+                    .map_err(|err| format!("invalid event: {}", err))?,
+            );
+        }
+    }
+    Ok((header, events))
+}
+
+/// 校验 DSN 中的密钥并限流, 在解析请求内容之前调用
+async fn authorize_sentry(
+    req: &HttpRequest,
+    app_data: &web::Data<AppState>,
+    project_id: i32,
+) -> actix_web::Result<()> {
+    if ingest_key_from_request(req).is_none() {
+        return Err(json_error(StatusCode::UNAUTHORIZED, "missing sentry_key"));
+    }
+    // 携带密钥时按密钥限流, 不需要包名
+    app_data.rate_limiter.check(req, [], 1)?;
+    if authorize_ingest(req, app_data).await? != project_id {
+        return Err(json_error(
+            StatusCode::FORBIDDEN,
+            "ingest key does not belong to this project",
+        ));
+    }
+    Ok(())
+}
+
+/// 保存事件, 返回 Sentry 格式的响应
+async fn ingest_events(
+    req: &HttpRequest,
+    app_data: &web::Data<AppState>,
+    project_id: i32,
+    event_id: &str,
+    events: Vec<Value>,
+) -> actix_web::Result<HttpResponse> {
+    // 超出限流的事件丢弃
+    let count = app_data.rate_limiter.check_items(req, [], events.len())?;
+    let ip = client_ip(req);
+    for event in events.iter().take(count) {
+        submit_log(app_data, project_id, &ip, upload_log_data(event)).await?;
+    }
+
+    Ok(HttpResponse::Ok()
+        .insert_header((ACCESS_CONTROL_ALLOW_ORIGIN, "*"))
+        .json(serde_json::json!({ "id": event_id })))
+}
+
+/// Sentry SDK 上报单个事件
+#[post("/api/{project_id:\\d+}/store/")]
+pub async fn api_sentry_store(
+    req: HttpRequest,
+    app_data: web::Data<AppState>,
+    path: web::Path<i32>,
+    body: web::Bytes,
+) -> actix_web::Result<HttpResponse> {
+    let project_id = path.into_inner();
+    authorize_sentry(&req, &app_data, project_id).await?;
+
+    let event: Value = serde_json::from_slice(&body)
+        .map_err(|err| json_error(StatusCode::BAD_REQUEST, &err.to_string()))?;
+    let event_id = str_field(&event, "/event_id").to_string();
+
+    ingest_events(&req, &app_data, project_id, &event_id, vec![event]).await
+}
+
+/// Sentry SDK 以 envelope 格式上报, 只处理其中的 event 条目
+#[post("/api/{project_id:\\d+}/envelope/")]
+pub async fn api_sentry_envelope(
+    req: HttpRequest,
+    app_data: web::Data<AppState>,
+    path: web::Path<i32>,
+    body: web::Bytes,
+) -> actix_web::Result<HttpResponse> {
+    let project_id = path.into_inner();
+    authorize_sentry(&req, &app_data, project_id).await?;
+
+    let (header, events) =
+        parse_envelope(&body).map_err(|err| json_error(StatusCode::BAD_REQUEST, &err))?;
+    let event_id = [
+        str_field(&header, "/event_id"),
+        events.first().map_or("", |x| str_field(x, "/event_id")),
+    ]
+    .into_iter()
+    .find(|x| !x.is_empty())
+    .unwrap_or_default()
+    .to_string();
+
+    ingest_events(&req, &app_data, project_id, &event_id, events).await
+}
@@ -0,0 +1,208 @@
+use crate::api::{user_authentication, AppState};
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use actix_web_httpauth::extractors::basic::BasicAuth;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// 统计上报速率的窗口
+const WINDOW: Duration = Duration::from_secs(60);
+
+struct ProjectRate {
+    window_start: Instant,
+    // 当前窗口内的上报次数
+    count: u64,
+    // 突增开始及预计结束的时间
+    spike: Option<(Instant, Instant)>,
+    // 突增期间丢弃日志内容的上报次数
+    dropped: u64,
+}
+
+/// 按项目检测上报突增
+///
+/// 项目一分钟内的上报次数超过阈值时进入突增状态, 直到连续 cooldown 时间未超过阈值;
+/// 突增期间的上报仍然计数, 但不保存日志内容
+pub struct SpikeDetector {
+    // 每分钟上报次数阈值, 0 表示不检测
+    threshold: u64,
+    cooldown: Duration,
+    projects: Mutex<HashMap<i32, ProjectRate>>,
+}
+
+impl SpikeDetector {
+    pub fn new(threshold: u64, cooldown: Duration) -> Self {
+        Self {
+            threshold,
+            cooldown,
+            projects: Default::default(),
+        }
+    }
+
+    /// 记录项目的一次上报, 返回项目当前是否处于突增状态
+    pub fn observe(&self, project_id: i32) -> bool {
+        self.observe_at(project_id, Instant::now())
+    }
+
+    fn observe_at(&self, project_id: i32, now: Instant) -> bool {
+        if self.threshold == 0 {
+            return false;
+        }
+
+        let mut projects = self.projects.lock().unwrap();
+        let rate = projects.entry(project_id).or_insert(ProjectRate {
+            window_start: now,
+            count: 0,
+            spike: None,
+            dropped: 0,
+        });
+
+        if now.duration_since(rate.window_start) >= WINDOW {
+            rate.window_start = now;
+            rate.count = 0;
+        }
+        rate.count += 1;
+
+        if rate.count > self.threshold {
+            let since = rate.spike.map_or(now, |(since, _)| since);
+            rate.spike = Some((since, now + self.cooldown));
+        } else if rate.spike.is_some_and(|(_, until)| until <= now) {
+            rate.spike = None;
+        }
+
+        if rate.spike.is_some() {
+            rate.dropped += 1;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct SpikeStatusItemData {
+    project_id: i32,
+    // 当前一分钟窗口内的上报次数
+    count: u64,
+    in_spike: bool,
+    // 突增已持续的秒数
+    spike_seconds: u64,
+    // 服务启动以来突增期间丢弃日志内容的上报次数
+    dropped: u64,
+}
+
+#[derive(Serialize, Debug)]
+struct SpikeStatusResponseData {
+    threshold: u64,
+    cooldown: u64,
+    projects: Vec<SpikeStatusItemData>,
+}
+
+/// 各项目的上报速率及突增状态
+#[get("/api/spike_status")]
+pub async fn api_spike_status(
+    req: HttpRequest,
+    credentials: BasicAuth,
+    app_data: web::Data<AppState>,
+) -> actix_web::Result<HttpResponse> {
+    if !user_authentication(&req, &credentials, &app_data)
+        .await?
+        .is_admin()
+    {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let detector = &app_data.spike_detector;
+    let now = Instant::now();
+    let mut projects: Vec<SpikeStatusItemData> = detector
+        .projects
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(project_id, rate)| {
+            let spike = rate.spike.filter(|(_, until)| *until > now);
+            SpikeStatusItemData {
+                project_id: *project_id,
+                count: if now.duration_since(rate.window_start) >= WINDOW {
+                    0
+                } else {
+                    rate.count
+                },
+                in_spike: spike.is_some(),
+                spike_seconds: spike.map_or(0, |(since, _)| now.duration_since(since).as_secs()),
+                dropped: rate.dropped,
+            }
+        })
+        .collect();
+    projects.sort_by_key(|x| x.project_id);
+
+    Ok(HttpResponse::Ok().json(SpikeStatusResponseData {
+        threshold: detector.threshold,
+        cooldown: detector.cooldown.as_secs(),
+        projects,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dropped(detector: &SpikeDetector, project_id: i32) -> u64 {
+        detector.projects.lock().unwrap()[&project_id].dropped
+    }
+
+    /// 超过阈值进入突增, 冷却时间内保持, 冷却结束后未超过阈值时退出
+    #[test]
+    fn spike_starts_and_cools_down() {
+        let detector = SpikeDetector::new(3, Duration::from_secs(120));
+        let start = Instant::now();
+
+        for _ in 0..3 {
+            assert!(!detector.observe_at(1, start));
+        }
+        assert!(detector.observe_at(1, start));
+        assert!(detector.observe_at(1, start + Duration::from_secs(30)));
+        // 其他项目不受影响
+        assert!(!detector.observe_at(2, start));
+
+        // 新窗口未超过阈值, 但仍在冷却时间内
+        assert!(detector.observe_at(1, start + Duration::from_secs(60)));
+        assert!(detector.observe_at(1, start + Duration::from_secs(149)));
+        assert!(!detector.observe_at(1, start + Duration::from_secs(150)));
+        assert_eq!(dropped(&detector, 1), 4);
+        assert_eq!(dropped(&detector, 2), 0);
+
+        // 窗口重置后重新计数
+        let later = start + Duration::from_secs(240);
+        for _ in 0..3 {
+            assert!(!detector.observe_at(1, later));
+        }
+        assert!(detector.observe_at(1, later));
+        assert_eq!(dropped(&detector, 1), 5);
+    }
+
+    /// 持续超过阈值时冷却时间从最后一次超过阈值时重新计算
+    #[test]
+    fn sustained_spike_extends_cooldown() {
+        let detector = SpikeDetector::new(1, Duration::from_secs(30));
+        let start = Instant::now();
+
+        assert!(!detector.observe_at(1, start));
+        assert!(detector.observe_at(1, start));
+        assert!(detector.observe_at(1, start + Duration::from_secs(50)));
+        let (since, until) = detector.projects.lock().unwrap()[&1].spike.unwrap();
+        assert_eq!(since, start);
+        assert_eq!(until, start + Duration::from_secs(80));
+
+        assert!(!detector.observe_at(1, start + Duration::from_secs(80)));
+    }
+
+    /// 阈值为 0 时不检测
+    #[test]
+    fn zero_threshold_disables_detection() {
+        let detector = SpikeDetector::new(0, Duration::from_secs(30));
+        let now = Instant::now();
+        assert!((0..100).all(|_| !detector.observe_at(1, now)));
+        assert!(detector.projects.lock().unwrap().is_empty());
+    }
+}
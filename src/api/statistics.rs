@@ -1,10 +1,23 @@
-use crate::api::{map_db_err, AppState};
+use crate::api::configuration_info::parse_configuration_info;
+use crate::api::project::authorize_ingest;
+use crate::api::release;
+use crate::api::statistics_device;
+use crate::api::{json_error, map_db_err, user_authentication, AppState};
+use crate::orm_entities::prelude::UploadStatisticsCliCfg;
 use crate::orm_entities::upload_statistics_cli_cfg;
-use actix_web::{post, web, HttpRequest, HttpResponse};
-use chrono::Utc;
+use actix_web::http::StatusCode;
+use actix_web::{get, post, web, HttpRequest, HttpResponse};
+use actix_web_httpauth::extractors::basic::BasicAuth;
+use chrono::{Days, NaiveDate, NaiveTime, Utc};
+use sea_orm::sea_query::Expr;
 use sea_orm::ActiveValue::Set;
-use sea_orm::{ActiveModelTrait, NotSet};
-use serde::Deserialize;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, Condition, EntityTrait, FromQueryResult, NotSet, PaginatorTrait,
+    QueryFilter, QueryOrder, QuerySelect, Select, TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
 
 #[derive(Deserialize, Debug)]
 struct UploadStatisticsCliCfgData {
@@ -18,6 +31,15 @@ struct UploadStatisticsCliCfgData {
     configuration_info: String,
     // 地区
     region: String,
+    // 设备id, 为空时按 用户 + 包名 识别设备
+    #[serde(default)]
+    device_id: String,
+    // 客户端启动时间(unix 时间戳, 秒), 为空或时钟异常时使用服务器接收时间
+    #[serde(default)]
+    time: Option<i64>,
+    // 客户端版本, 为空时不参与版本统计
+    #[serde(default)]
+    version: String,
 }
 
 #[post("/api/upload_statistics_cli_cfg")]
@@ -26,26 +48,476 @@ async fn api_upload_statistics(
     app_data: web::Data<AppState>,
     json_data: web::Json<UploadStatisticsCliCfgData>,
 ) -> actix_web::Result<HttpResponse> {
+    app_data
+        .rate_limiter
+        .check(&req, [json_data.package.as_str()], 1)?;
+    let project_id = authorize_ingest(&req, &app_data).await?;
+
     let ip = if let Some(x) = req.connection_info().realip_remote_addr() {
         x.to_string()
     } else {
         "unknown".to_string()
     };
 
+    let time = app_data
+        .clock
+        .resolve(json_data.time, Utc::now().naive_utc());
     let data = upload_statistics_cli_cfg::ActiveModel {
         id: NotSet,
         cli_type: Set(json_data.cli_type.to_owned()),
         user: Set(json_data.user.to_owned()),
         package: Set(json_data.package.to_owned()),
         configuration_info: Set(json_data.configuration_info.to_owned()),
+        configuration_json: Set(Value::Object(parse_configuration_info(
+            &json_data.configuration_info,
+        ))),
         ip: Set(ip),
         region: Set(json_data.region.to_owned()),
-        time: Set(Utc::now().naive_utc()),
+        time: Set(time.time),
+        project_id: Set(project_id),
+        device_id: Set(json_data.device_id.to_owned()),
+        receive_time: Set(time.receive_time),
+        clock_skew: Set(time.clock_skew),
+        version: Set(json_data.version.trim().to_string()),
     };
-    let _ = data
-        .save(app_data.db_pool.get().unwrap())
+
+    let txn = app_data
+        .db_pool
+        .get()
+        .unwrap()
+        .begin()
+        .await
+        .map_err(map_db_err)?;
+    let data = data.insert(&txn).await.map_err(map_db_err)?;
+    statistics_device::record_launch(&txn, &data)
+        .await
+        .map_err(map_db_err)?;
+    release::record_launch(&txn, &data)
         .await
         .map_err(map_db_err)?;
+    txn.commit().await.map_err(map_db_err)?;
 
     Ok(HttpResponse::Ok().body("{\"data\": \"ok\"}"))
 }
+
+#[derive(Deserialize, Debug)]
+pub struct StatisticsFilter {
+    cli_type: Option<String>,
+    package: Option<String>,
+    region: Option<String>,
+    project_id: Option<i32>,
+    // 起始日期(含), 格式 YYYY-MM-DD
+    start_date: Option<String>,
+    // 结束日期(含), 格式 YYYY-MM-DD
+    end_date: Option<String>,
+}
+
+fn parse_date(name: &str, value: &str) -> actix_web::Result<NaiveDate> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d").map_err(|_| {
+        json_error(
+            StatusCode::BAD_REQUEST,
+            &format!("invalid {}: {}, expected YYYY-MM-DD", name, value),
+        )
+    })
+}
+
+impl StatisticsFilter {
+    /// 客户端类型 / 包名 / 地区 / 项目 的过滤条件, 传入对应表的列
+    pub fn condition<C: ColumnTrait>(
+        &self,
+        cli_type: C,
+        package: C,
+        region: C,
+        project_id: C,
+    ) -> Condition {
+        let mut condition = Condition::all();
+        if let Some(x) = self.cli_type.as_ref().filter(|x| !x.is_empty()) {
+            condition = condition.add(cli_type.eq(x));
+        }
+        if let Some(x) = self.package.as_ref().filter(|x| !x.is_empty()) {
+            condition = condition.add(package.eq(x));
+        }
+        if let Some(x) = self.region.as_ref().filter(|x| !x.is_empty()) {
+            condition = condition.add(region.eq(x));
+        }
+        if let Some(x) = self.project_id {
+            condition = condition.add(project_id.eq(x));
+        }
+        condition
+    }
+
+    /// 日期范围, 返回 (起始日期, 结束日期的后一天)
+    pub fn date_range(&self) -> actix_web::Result<(Option<NaiveDate>, Option<NaiveDate>)> {
+        let start = match self.start_date.as_ref().filter(|x| !x.is_empty()) {
+            Some(x) => Some(parse_date("start_date", x)?),
+            None => None,
+        };
+        let end = match self.end_date.as_ref().filter(|x| !x.is_empty()) {
+            Some(x) => Some(parse_date("end_date", x)? + Days::new(1)),
+            None => None,
+        };
+        Ok((start, end))
+    }
+
+    /// 将过滤条件应用到查询上, 所有参数均以绑定参数传入
+    pub fn apply(
+        &self,
+        mut query: Select<UploadStatisticsCliCfg>,
+    ) -> actix_web::Result<Select<UploadStatisticsCliCfg>> {
+        query = query.filter(self.condition(
+            upload_statistics_cli_cfg::Column::CliType,
+            upload_statistics_cli_cfg::Column::Package,
+            upload_statistics_cli_cfg::Column::Region,
+            upload_statistics_cli_cfg::Column::ProjectId,
+        ));
+
+        let (start, end) = self.date_range()?;
+        if let Some(start) = start {
+            query = query.filter(
+                upload_statistics_cli_cfg::Column::Time.gte(start.and_time(NaiveTime::MIN)),
+            );
+        }
+        if let Some(end) = end {
+            query = query
+                .filter(upload_statistics_cli_cfg::Column::Time.lt(end.and_time(NaiveTime::MIN)));
+        }
+        Ok(query)
+    }
+}
+
+#[derive(FromQueryResult, Serialize, Debug)]
+struct DailyCountData {
+    date: String,
+    count: i64,
+}
+
+#[derive(Serialize, Debug)]
+struct DailyResponseData {
+    success: bool,
+    items: Vec<DailyCountData>,
+}
+
+#[get("/api/statistics/daily")]
+pub async fn api_statistics_daily(
+    req: HttpRequest,
+    credentials: BasicAuth,
+    app_data: web::Data<AppState>,
+    filter: web::Query<StatisticsFilter>,
+) -> actix_web::Result<HttpResponse> {
+    if !user_authentication(&req, &credentials, &app_data)
+        .await?
+        .is_admin()
+    {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let query = UploadStatisticsCliCfg::find()
+        .select_only()
+        .column_as(Expr::cust("DATE(time)"), "date")
+        .column_as(upload_statistics_cli_cfg::Column::Id.count(), "count")
+        .group_by(Expr::cust("DATE(time)"))
+        .order_by_asc(Expr::cust("DATE(time)"));
+
+    let items = filter
+        .apply(query)?
+        .into_model::<DailyCountData>()
+        .all(app_data.db_pool.get().unwrap())
+        .await
+        .map_err(map_db_err)?;
+
+    Ok(HttpResponse::Ok().json(DailyResponseData {
+        success: true,
+        items,
+    }))
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum CapabilityGroupBy {
+    #[default]
+    CliType,
+    Package,
+}
+
+#[derive(Deserialize, Debug)]
+struct CapabilitiesRequestData {
+    // 分组方式, 默认按客户端类型
+    #[serde(default)]
+    group_by: CapabilityGroupBy,
+}
+
+#[derive(Serialize, Debug)]
+struct CapabilityItemData {
+    key: String,
+    // 该项上报了布尔值的数量, 缺少该项或不是布尔值的上报不计入
+    reported: u64,
+    supported: u64,
+    unsupported: u64,
+    percent: f64,
+}
+
+#[derive(Serialize, Debug)]
+struct CapabilityGroupData {
+    name: String,
+    // 上报总数
+    total: u64,
+    // 上报了配置信息的数量
+    reported: u64,
+    items: Vec<CapabilityItemData>,
+}
+
+#[derive(Serialize, Debug)]
+struct CapabilitiesResponseData {
+    success: bool,
+    all: CapabilityGroupData,
+    groups: Vec<CapabilityGroupData>,
+}
+
+/// 统计配置信息中所有布尔项的支持数量
+#[derive(Default, Debug)]
+struct CapabilityCounter {
+    total: u64,
+    reported: u64,
+    // 项名 -> (上报了该项的数量, 支持的数量)
+    items: BTreeMap<String, (u64, u64)>,
+}
+
+impl CapabilityCounter {
+    fn add(&mut self, info: &Map<String, Value>) {
+        self.total += 1;
+        if info.is_empty() {
+            return;
+        }
+        self.reported += 1;
+        for (key, value) in info {
+            if let Value::Bool(supported) = value {
+                let (reported, count) = self.items.entry(key.clone()).or_default();
+                *reported += 1;
+                *count += u64::from(*supported);
+            }
+        }
+    }
+
+    fn into_data(self, name: String) -> CapabilityGroupData {
+        CapabilityGroupData {
+            name,
+            total: self.total,
+            reported: self.reported,
+            items: self
+                .items
+                .into_iter()
+                .map(|(key, (reported, supported))| CapabilityItemData {
+                    key,
+                    reported,
+                    supported,
+                    unsupported: reported - supported,
+                    percent: if reported == 0 {
+                        0.0
+                    } else {
+                        supported as f64 / reported as f64 * 100.0
+                    },
+                })
+                .collect(),
+        }
+    }
+}
+
+#[get("/api/statistics/capabilities")]
+pub async fn api_statistics_capabilities(
+    req: HttpRequest,
+    credentials: BasicAuth,
+    app_data: web::Data<AppState>,
+    filter: web::Query<StatisticsFilter>,
+    json_data: web::Query<CapabilitiesRequestData>,
+) -> actix_web::Result<HttpResponse> {
+    if !user_authentication(&req, &credentials, &app_data)
+        .await?
+        .is_admin()
+    {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let query = UploadStatisticsCliCfg::find()
+        .select_only()
+        .columns([
+            upload_statistics_cli_cfg::Column::CliType,
+            upload_statistics_cli_cfg::Column::Package,
+            upload_statistics_cli_cfg::Column::ConfigurationJson,
+        ])
+        .order_by_asc(upload_statistics_cli_cfg::Column::Id);
+
+    let mut all = CapabilityCounter::default();
+    let mut groups: BTreeMap<String, CapabilityCounter> = BTreeMap::new();
+
+    // 分批读取, 避免一次性加载全部记录
+    let mut pages = filter
+        .apply(query)?
+        .into_tuple::<(String, String, Value)>()
+        .paginate(app_data.db_pool.get().unwrap(), 1000);
+    while let Some(rows) = pages.fetch_and_next().await.map_err(map_db_err)? {
+        for (cli_type, package, configuration) in rows {
+            let info = match configuration {
+                Value::Object(info) => info,
+                _ => Map::new(),
+            };
+            let name = match json_data.group_by {
+                CapabilityGroupBy::CliType => cli_type,
+                CapabilityGroupBy::Package => package,
+            };
+            all.add(&info);
+            groups.entry(name).or_default().add(&info);
+        }
+    }
+
+    Ok(HttpResponse::Ok().json(CapabilitiesResponseData {
+        success: true,
+        all: all.into_data("all".into()),
+        groups: groups
+            .into_iter()
+            .map(|(name, counter)| counter.into_data(name))
+            .collect(),
+    }))
+}
+
+#[derive(Deserialize, Debug)]
+struct DevicesRequestData {
+    page: Option<u64>,
+    page_size: Option<u64>,
+    // 配置项名称, 如 supports_ASTC
+    key: String,
+    // 配置项的值, 为空时匹配包含该配置项的设备
+    value: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+struct DeviceItemData {
+    id: u32,
+    cli_type: String,
+    user: String,
+    package: String,
+    region: String,
+    time: i64,
+    configuration: Value,
+}
+
+#[derive(Serialize, Debug)]
+struct DevicesResponseData {
+    success: bool,
+    total: u64,
+    items: Vec<DeviceItemData>,
+}
+
+/// 查询配置项满足条件的上报记录, 例如 `?key=supports_ASTC&value=false`
+#[get("/api/statistics/devices")]
+pub async fn api_statistics_devices(
+    req: HttpRequest,
+    credentials: BasicAuth,
+    app_data: web::Data<AppState>,
+    filter: web::Query<StatisticsFilter>,
+    json_data: web::Query<DevicesRequestData>,
+) -> actix_web::Result<HttpResponse> {
+    if !user_authentication(&req, &credentials, &app_data)
+        .await?
+        .is_admin()
+    {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let key = json_data.key.trim();
+    if key.is_empty()
+        || !key
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '.')
+    {
+        return Err(json_error(StatusCode::BAD_REQUEST, "invalid key"));
+    }
+    let path = format!("$.\"{}\"", key);
+
+    let condition = match json_data.value.as_deref() {
+        Some(value) => {
+            // 与入库时相同的规则解析值的类型
+            let value = match parse_configuration_info(&format!("{}: {}", key, value)).remove(key) {
+                Some(Value::Bool(x)) => sea_orm::Value::from(x),
+                Some(Value::Number(x)) => sea_orm::Value::from(x.as_i64()),
+                _ => sea_orm::Value::from(value.to_string()),
+            };
+            Expr::cust_with_values(
+                "json_extract(configuration_json, ?) = ?",
+                [sea_orm::Value::from(path), value],
+            )
+        }
+        None => Expr::cust_with_values(
+            "json_extract(configuration_json, ?) IS NOT NULL",
+            [sea_orm::Value::from(path)],
+        ),
+    };
+
+    let query = filter.apply(
+        UploadStatisticsCliCfg::find()
+            .filter(condition)
+            .order_by_desc(upload_statistics_cli_cfg::Column::Id),
+    )?;
+
+    let page = json_data.page.unwrap_or(1).max(1);
+    let page_size = json_data.page_size.unwrap_or(20).clamp(1, 100);
+    let paginator = query.paginate(app_data.db_pool.get().unwrap(), page_size);
+    let total = paginator.num_items().await.map_err(map_db_err)?;
+    let items = paginator
+        .fetch_page(page - 1)
+        .await
+        .map_err(map_db_err)?
+        .into_iter()
+        .map(|x| DeviceItemData {
+            id: x.id,
+            cli_type: x.cli_type,
+            user: x.user,
+            package: x.package,
+            region: x.region,
+            time: x.time.and_utc().timestamp(),
+            configuration: x.configuration_json,
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(DevicesResponseData {
+        success: true,
+        total,
+        items,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn info(value: Value) -> Map<String, Value> {
+        match value {
+            Value::Object(info) => info,
+            _ => unreachable!(),
+        }
+    }
+
+    /// 缺少某项或该项不是布尔值的上报不计入该项的统计
+    #[test]
+    fn capability_counts_only_reports_with_the_key() {
+        let mut counter = CapabilityCounter::default();
+        counter.add(&info(json!({ "gl": true, "vr": true })));
+        counter.add(&info(json!({ "gl": false, "vr": "unknown" })));
+        counter.add(&info(json!({ "gl": true })));
+        counter.add(&Map::new());
+
+        let data = counter.into_data("all".into());
+        assert_eq!(data.total, 4);
+        assert_eq!(data.reported, 3);
+
+        let gl = &data.items[0];
+        assert_eq!(gl.key, "gl");
+        assert_eq!((gl.reported, gl.supported, gl.unsupported), (3, 2, 1));
+        assert!((gl.percent - 200.0 / 3.0).abs() < 1e-9);
+
+        let vr = &data.items[1];
+        assert_eq!(vr.key, "vr");
+        assert_eq!((vr.reported, vr.supported, vr.unsupported), (1, 1, 0));
+        assert_eq!(vr.percent, 100.0);
+    }
+}
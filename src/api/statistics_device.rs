@@ -0,0 +1,293 @@
+use crate::api::statistics::StatisticsFilter;
+use crate::api::{json_error, map_db_err, user_authentication, AppState};
+use crate::orm_entities::prelude::{StatisticsDevice, StatisticsDeviceDay};
+use crate::orm_entities::{statistics_device, statistics_device_day, upload_statistics_cli_cfg};
+use actix_web::http::StatusCode;
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use actix_web_httpauth::extractors::basic::BasicAuth;
+use chrono::NaiveTime;
+use sea_orm::sea_query::{Expr, OnConflict};
+use sea_orm::ActiveValue::Set;
+use sea_orm::{
+    ColumnTrait, ConnectionTrait, DbErr, EntityTrait, FromQueryResult, NotSet, QueryFilter,
+    QueryOrder, QuerySelect, QueryTrait,
+};
+use serde::{Deserialize, Serialize};
+
+/// 记录一次客户端启动, 更新设备的活跃时间和当天的活跃记录
+pub async fn record_launch<C: ConnectionTrait>(
+    db: &C,
+    data: &upload_statistics_cli_cfg::Model,
+) -> Result<(), DbErr> {
+    StatisticsDevice::insert(statistics_device::ActiveModel {
+        id: NotSet,
+        project_id: Set(data.project_id),
+        package: Set(data.package.clone()),
+        user: Set(data.user.clone()),
+        device_id: Set(data.device_id.clone()),
+        cli_type: Set(data.cli_type.clone()),
+        region: Set(data.region.clone()),
+        first_seen: Set(data.time),
+        last_seen: Set(data.time),
+        launch_count: Set(1),
+    })
+    .on_conflict(
+        OnConflict::columns([
+            statistics_device::Column::ProjectId,
+            statistics_device::Column::Package,
+            statistics_device::Column::User,
+            statistics_device::Column::DeviceId,
+        ])
+        .update_columns([
+            statistics_device::Column::CliType,
+            statistics_device::Column::Region,
+        ])
+        // 离线缓存的上报可能早于已记录的时间
+        .value(
+            statistics_device::Column::FirstSeen,
+            Expr::cust("MIN(first_seen, excluded.first_seen)"),
+        )
+        .value(
+            statistics_device::Column::LastSeen,
+            Expr::cust("MAX(last_seen, excluded.last_seen)"),
+        )
+        .value(
+            statistics_device::Column::LaunchCount,
+            Expr::col(statistics_device::Column::LaunchCount).add(1),
+        )
+        .to_owned(),
+    )
+    .exec_without_returning(db)
+    .await?;
+
+    let device = StatisticsDevice::find()
+        .filter(statistics_device::Column::ProjectId.eq(data.project_id))
+        .filter(statistics_device::Column::Package.eq(&data.package))
+        .filter(statistics_device::Column::User.eq(&data.user))
+        .filter(statistics_device::Column::DeviceId.eq(&data.device_id))
+        .one(db)
+        .await?
+        .ok_or(DbErr::RecordNotFound("statistics_device".into()))?;
+
+    StatisticsDeviceDay::insert(statistics_device_day::ActiveModel {
+        id: NotSet,
+        device_id: Set(device.id),
+        date: Set(data.time.date()),
+    })
+    .on_conflict(
+        OnConflict::columns([
+            statistics_device_day::Column::DeviceId,
+            statistics_device_day::Column::Date,
+        ])
+        .do_nothing()
+        .to_owned(),
+    )
+    .exec_without_returning(db)
+    .await?;
+
+    Ok(())
+}
+
+fn device_condition(filter: &StatisticsFilter) -> sea_orm::Condition {
+    filter.condition(
+        statistics_device::Column::CliType,
+        statistics_device::Column::Package,
+        statistics_device::Column::Region,
+        statistics_device::Column::ProjectId,
+    )
+}
+
+#[derive(FromQueryResult, Serialize, Debug)]
+struct ActiveDailyData {
+    date: String,
+    // 当天活跃设备数
+    active_users: i64,
+    // 当天新增设备数
+    new_users: i64,
+}
+
+#[derive(Serialize, Debug)]
+struct ActiveResponseData {
+    success: bool,
+    items: Vec<ActiveDailyData>,
+}
+
+/// 每日活跃设备数及新增设备数
+#[get("/api/statistics/active")]
+pub async fn api_statistics_active(
+    req: HttpRequest,
+    credentials: BasicAuth,
+    app_data: web::Data<AppState>,
+    filter: web::Query<StatisticsFilter>,
+) -> actix_web::Result<HttpResponse> {
+    if !user_authentication(&req, &credentials, &app_data)
+        .await?
+        .is_admin()
+    {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let mut query = StatisticsDeviceDay::find()
+        .select_only()
+        .column_as(statistics_device_day::Column::Date, "date")
+        .column_as(Expr::cust("COUNT(*)"), "active_users")
+        .column_as(
+            Expr::cust("SUM(DATE(statistics_device.first_seen) = statistics_device_day.date)"),
+            "new_users",
+        )
+        .inner_join(StatisticsDevice)
+        .filter(device_condition(&filter))
+        .group_by(statistics_device_day::Column::Date)
+        .order_by_asc(statistics_device_day::Column::Date);
+
+    let (start, end) = filter.date_range()?;
+    if let Some(start) = start {
+        query = query.filter(statistics_device_day::Column::Date.gte(start));
+    }
+    if let Some(end) = end {
+        query = query.filter(statistics_device_day::Column::Date.lt(end));
+    }
+
+    let items = query
+        .into_model::<ActiveDailyData>()
+        .all(app_data.db_pool.get().unwrap())
+        .await
+        .map_err(map_db_err)?;
+
+    Ok(HttpResponse::Ok().json(ActiveResponseData {
+        success: true,
+        items,
+    }))
+}
+
+#[derive(Deserialize, Debug)]
+struct RetentionRequestData {
+    // 留存天数, 逗号分隔, 默认 1,7,30
+    days: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+struct RetentionItemData {
+    day: u32,
+    retained: i64,
+    percent: f64,
+}
+
+#[derive(Serialize, Debug)]
+struct RetentionCohortData {
+    // 首次启动日期
+    cohort: String,
+    new_users: i64,
+    retention: Vec<RetentionItemData>,
+}
+
+#[derive(Serialize, Debug)]
+struct RetentionResponseData {
+    success: bool,
+    items: Vec<RetentionCohortData>,
+}
+
+/// 按首次启动日期分组的留存, 日期范围过滤的是首次启动日期
+#[get("/api/statistics/retention")]
+pub async fn api_statistics_retention(
+    req: HttpRequest,
+    credentials: BasicAuth,
+    app_data: web::Data<AppState>,
+    filter: web::Query<StatisticsFilter>,
+    json_data: web::Query<RetentionRequestData>,
+) -> actix_web::Result<HttpResponse> {
+    if !user_authentication(&req, &credentials, &app_data)
+        .await?
+        .is_admin()
+    {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let days: Vec<u32> = match json_data.days.as_deref().filter(|x| !x.is_empty()) {
+        Some(days) => days
+            .split(',')
+            .map(|x| {
+                x.trim()
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|x| (1..=365).contains(x))
+            })
+            .collect::<Option<Vec<_>>>()
+            .filter(|x| x.len() <= 10)
+            .ok_or_else(|| {
+                json_error(
+                    StatusCode::BAD_REQUEST,
+                    "invalid days, expected at most 10 numbers between 1 and 365",
+                )
+            })?,
+        None => vec![1, 7, 30],
+    };
+
+    let mut query = StatisticsDevice::find()
+        .select_only()
+        .column_as(Expr::cust("DATE(statistics_device.first_seen)"), "cohort")
+        .column_as(Expr::cust("COUNT(*)"), "new_users");
+    for day in &days {
+        query = query.column_as(
+            Expr::cust_with_values(
+                "SUM(EXISTS(
+                    SELECT 1 FROM statistics_device_day
+                    WHERE statistics_device_day.device_id = statistics_device.id
+                    AND statistics_device_day.date = DATE(statistics_device.first_seen, ?)
+                ))",
+                [format!("+{} day", day)],
+            ),
+            format!("day_{}", day),
+        );
+    }
+
+    let mut query = query
+        .filter(device_condition(&filter))
+        .group_by(Expr::cust("DATE(statistics_device.first_seen)"))
+        .order_by_asc(Expr::cust("DATE(statistics_device.first_seen)"));
+
+    let (start, end) = filter.date_range()?;
+    if let Some(start) = start {
+        query =
+            query.filter(statistics_device::Column::FirstSeen.gte(start.and_time(NaiveTime::MIN)));
+    }
+    if let Some(end) = end {
+        query = query.filter(statistics_device::Column::FirstSeen.lt(end.and_time(NaiveTime::MIN)));
+    }
+
+    let db = app_data.db_pool.get().unwrap();
+    let rows = db
+        .query_all(query.build(db.get_database_backend()))
+        .await
+        .map_err(map_db_err)?;
+
+    let mut items = Vec::with_capacity(rows.len());
+    for row in rows {
+        let new_users: i64 = row.try_get("", "new_users").map_err(map_db_err)?;
+        let mut retention = Vec::with_capacity(days.len());
+        for day in &days {
+            let retained: i64 = row
+                .try_get("", &format!("day_{}", day))
+                .map_err(map_db_err)?;
+            retention.push(RetentionItemData {
+                day: *day,
+                retained,
+                percent: if new_users == 0 {
+                    0.0
+                } else {
+                    retained as f64 / new_users as f64 * 100.0
+                },
+            });
+        }
+        items.push(RetentionCohortData {
+            cohort: row.try_get("", "cohort").map_err(map_db_err)?,
+            new_users,
+            retention,
+        });
+    }
+
+    Ok(HttpResponse::Ok().json(RetentionResponseData {
+        success: true,
+        items,
+    }))
+}
@@ -0,0 +1,332 @@
+use crate::api::{json_error, map_db_err, user_authentication, AppState};
+use crate::orm_entities::package_version_scheme;
+use crate::orm_entities::prelude::PackageVersionScheme;
+use crate::orm_entities::sea_orm_active_enums::VersionScheme;
+use actix_web::http::StatusCode;
+use actix_web::{post, web, HttpRequest, HttpResponse};
+use actix_web_httpauth::extractors::basic::BasicAuth;
+use sea_orm::sea_query::OnConflict;
+use sea_orm::ActiveValue::Set;
+use sea_orm::{ConnectionTrait, DbErr, EntityTrait, NotSet, QueryOrder};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+impl VersionScheme {
+    /// 按该方式比较两个版本号
+    pub fn compare(self, a: &str, b: &str) -> Ordering {
+        match self {
+            Self::Semver => compare_semver(a, b),
+            Self::BuildNumber => build_number(a).cmp(&build_number(b)),
+        }
+    }
+}
+
+/// 宽松的语义化版本比较
+///
+/// 忽略前缀 `v` 及 `+` 之后的构建信息, 主版本部分为任意段数的数字(缺少的段视为 0),
+/// 紧跟 `-` 的为预发布版本, 低于对应的正式版本, 其余后缀忽略
+fn compare_semver(a: &str, b: &str) -> Ordering {
+    let (a_core, a_pre) = parse_semver(a);
+    let (b_core, b_pre) = parse_semver(b);
+
+    let len = a_core.len().max(b_core.len());
+    let core = (0..len)
+        .map(|i| {
+            let x = a_core.get(i).copied().unwrap_or_default();
+            let y = b_core.get(i).copied().unwrap_or_default();
+            x.cmp(&y)
+        })
+        .find(|x| x.is_ne())
+        .unwrap_or(Ordering::Equal);
+
+    core.then_with(|| match (a_pre, b_pre) {
+        (None, None) => Ordering::Equal,
+        (None, Some(_)) => Ordering::Greater,
+        (Some(_), None) => Ordering::Less,
+        (Some(a), Some(b)) => {
+            let mut a = a.split('.');
+            let mut b = b.split('.');
+            loop {
+                match (a.next(), b.next()) {
+                    (None, None) => return Ordering::Equal,
+                    (None, Some(_)) => return Ordering::Less,
+                    (Some(_), None) => return Ordering::Greater,
+                    (Some(x), Some(y)) => {
+                        // 数字标识按数值比较, 且低于非数字标识
+                        let order = match (x.parse::<u64>(), y.parse::<u64>()) {
+                            (Ok(x), Ok(y)) => x.cmp(&y),
+                            (Ok(_), Err(_)) => Ordering::Less,
+                            (Err(_), Ok(_)) => Ordering::Greater,
+                            (Err(_), Err(_)) => x.cmp(y),
+                        };
+                        if order.is_ne() {
+                            return order;
+                        }
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// 拆分为主版本的各段数字及预发布标识
+fn parse_semver(version: &str) -> (Vec<u64>, Option<&str>) {
+    let version = version.trim().trim_start_matches(['v', 'V']);
+    let version = version.split_once('+').map_or(version, |(x, _)| x);
+    let end = version
+        .find(|x: char| !x.is_ascii_digit() && x != '.')
+        .unwrap_or(version.len());
+
+    let core = version[..end]
+        .split('.')
+        .filter(|x| !x.is_empty())
+        .map(|x| x.parse().unwrap_or(u64::MAX))
+        .collect();
+    let pre = version[end..].strip_prefix('-').filter(|x| !x.is_empty());
+    (core, pre)
+}
+
+/// 版本号中的最后一段数字作为构建号, 如 `1.2.0 (345)` 为 345, 没有数字时为 0
+fn build_number(version: &str) -> u64 {
+    version
+        .rsplit(|x: char| !x.is_ascii_digit())
+        .find(|x| !x.is_empty())
+        .map_or(0, |x| x.parse().unwrap_or(u64::MAX))
+}
+
+/// 按包名配置的版本号比较方式, 未配置的包名使用默认方式
+pub struct VersionSchemes {
+    default_scheme: VersionScheme,
+    schemes: RwLock<HashMap<String, VersionScheme>>,
+}
+
+impl VersionSchemes {
+    pub fn new(default_scheme: VersionScheme) -> Self {
+        Self {
+            default_scheme,
+            schemes: Default::default(),
+        }
+    }
+
+    /// 从数据库重新加载配置, 配置变更后调用
+    pub async fn reload<C: ConnectionTrait>(&self, db: &C) -> Result<(), DbErr> {
+        let schemes = PackageVersionScheme::find()
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|x| (x.package, x.scheme))
+            .collect();
+        *self.schemes.write().unwrap() = schemes;
+        Ok(())
+    }
+
+    pub fn scheme(&self, package: &str) -> VersionScheme {
+        self.schemes
+            .read()
+            .unwrap()
+            .get(package)
+            .copied()
+            .unwrap_or(self.default_scheme)
+    }
+
+    /// 按包名对应的方式比较两个版本号
+    pub fn compare(&self, package: &str, a: &str, b: &str) -> Ordering {
+        self.scheme(package).compare(a, b)
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct VersionSchemeItemData {
+    id: i32,
+    package: String,
+    scheme: VersionScheme,
+}
+
+#[derive(Serialize, Debug)]
+struct VersionSchemeListResponseData {
+    // 未配置的包名使用的比较方式
+    default_scheme: VersionScheme,
+    items: Vec<VersionSchemeItemData>,
+}
+
+#[post("/api/version_scheme_list")]
+pub async fn api_version_scheme_list(
+    req: HttpRequest,
+    credentials: BasicAuth,
+    app_data: web::Data<AppState>,
+) -> actix_web::Result<HttpResponse> {
+    if !user_authentication(&req, &credentials, &app_data)
+        .await?
+        .is_admin()
+    {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let items = PackageVersionScheme::find()
+        .order_by_asc(package_version_scheme::Column::Package)
+        .all(app_data.db_pool.get().unwrap())
+        .await
+        .map_err(map_db_err)?
+        .into_iter()
+        .map(|x| VersionSchemeItemData {
+            id: x.id,
+            package: x.package,
+            scheme: x.scheme,
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(VersionSchemeListResponseData {
+        default_scheme: app_data.version_schemes.default_scheme,
+        items,
+    }))
+}
+
+#[derive(Deserialize, Debug)]
+struct VersionSchemeSaveRequestData {
+    package: String,
+    scheme: VersionScheme,
+}
+
+/// 新增或修改包名的版本号比较方式
+#[post("/api/version_scheme_save")]
+pub async fn api_version_scheme_save(
+    req: HttpRequest,
+    credentials: BasicAuth,
+    app_data: web::Data<AppState>,
+    json_data: web::Json<VersionSchemeSaveRequestData>,
+) -> actix_web::Result<HttpResponse> {
+    if !user_authentication(&req, &credentials, &app_data)
+        .await?
+        .is_admin()
+    {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let package = json_data.package.trim();
+    if package.is_empty() {
+        return Err(json_error(StatusCode::BAD_REQUEST, "package is empty"));
+    }
+
+    let db = app_data.db_pool.get().unwrap();
+    PackageVersionScheme::insert(package_version_scheme::ActiveModel {
+        id: NotSet,
+        package: Set(package.to_string()),
+        scheme: Set(json_data.scheme),
+    })
+    .on_conflict(
+        OnConflict::column(package_version_scheme::Column::Package)
+            .update_column(package_version_scheme::Column::Scheme)
+            .to_owned(),
+    )
+    .exec_without_returning(db)
+    .await
+    .map_err(map_db_err)?;
+
+    app_data
+        .version_schemes
+        .reload(db)
+        .await
+        .map_err(map_db_err)?;
+
+    Ok(HttpResponse::Ok().body("{\"data\": \"ok\"}"))
+}
+
+#[derive(Deserialize, Debug)]
+struct VersionSchemeRemoveRequestData {
+    id: i32,
+}
+
+#[post("/api/version_scheme_remove")]
+pub async fn api_version_scheme_remove(
+    req: HttpRequest,
+    credentials: BasicAuth,
+    app_data: web::Data<AppState>,
+    json_data: web::Json<VersionSchemeRemoveRequestData>,
+) -> actix_web::Result<HttpResponse> {
+    if !user_authentication(&req, &credentials, &app_data)
+        .await?
+        .is_admin()
+    {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let db = app_data.db_pool.get().unwrap();
+    PackageVersionScheme::delete_by_id(json_data.id)
+        .exec(db)
+        .await
+        .map_err(map_db_err)?;
+
+    app_data
+        .version_schemes
+        .reload(db)
+        .await
+        .map_err(map_db_err)?;
+
+    Ok(HttpResponse::Ok().body("{\"data\": \"ok\"}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Ordering::{Equal, Greater, Less};
+
+    fn check(scheme: VersionScheme, cases: &[(&str, &str, Ordering)]) {
+        for (a, b, expected) in cases {
+            assert_eq!(scheme.compare(a, b), *expected, "{} vs {}", a, b);
+            assert_eq!(scheme.compare(b, a), expected.reverse(), "{} vs {}", b, a);
+        }
+    }
+
+    #[test]
+    fn compares_semver() {
+        check(
+            VersionScheme::Semver,
+            &[
+                ("1.2.3", "1.2.3", Equal),
+                ("1.10.0", "1.9.0", Greater),
+                ("2.0.0", "1.99.99", Greater),
+                // 缺少的段视为 0
+                ("1.2", "1.2.0", Equal),
+                ("1", "1.0.0.0", Equal),
+                ("1.2", "1.2.1", Less),
+                // 前缀 v
+                ("v1.2.3", "1.2.3", Equal),
+                ("V2.0", "1.9", Greater),
+                // 构建信息
+                ("1.2.3+build.5", "1.2.3+build.9", Equal),
+                ("1.2.3+99", "1.2.4", Less),
+                ("1.2.3-rc.1+build", "1.2.3-rc.1", Equal),
+                // 预发布版本
+                ("1.0.0-alpha", "1.0.0", Less),
+                ("1.0.0-rc.1", "0.9.9", Greater),
+                ("1.0.0-alpha", "1.0.0-alpha.1", Less),
+                ("1.0.0-alpha.1", "1.0.0-alpha.beta", Less),
+                ("1.0.0-beta", "1.0.0-alpha", Greater),
+                ("1.0.0-rc.10", "1.0.0-rc.9", Greater),
+                // 非数字的段及后缀
+                ("1.2.x", "1.2", Equal),
+                ("1.2beta", "1.2", Equal),
+                ("abc", "0.0.1", Less),
+                ("", "0", Equal),
+                ("99999999999999999999", "1", Greater),
+            ],
+        );
+    }
+
+    #[test]
+    fn compares_build_numbers() {
+        check(
+            VersionScheme::BuildNumber,
+            &[
+                ("1.2.0 (345)", "1.3.0 (344)", Greater),
+                ("345", "1000", Less),
+                ("build 12", "12", Equal),
+                ("2.0", "1.99", Less),
+                ("release", "1", Less),
+                ("", "0", Equal),
+            ],
+        );
+    }
+}
@@ -1,12 +1,24 @@
 mod api;
+mod migration;
 mod orm_entities;
 
-use crate::api::AppState;
-use crate::orm_entities::{upload_log, upload_statistics_cli_cfg, upload_user};
+use crate::api::fingerprint::Fingerprinter;
+use crate::api::ingest_queue::IngestQueue;
+use crate::api::log_type::LogTypeRules;
+use crate::api::rate_limit::{RateLimit, RateLimiter};
+use crate::api::spike::SpikeDetector;
+use crate::api::version::VersionSchemes;
+use crate::api::{account, regroup, AppState, ClockPolicy, SamplingPolicy};
+use crate::migration::{Migrator, MigratorTrait};
+use crate::orm_entities::prelude::{Account, AccountProject};
+use crate::orm_entities::sea_orm_active_enums::{LogAction, VersionScheme};
 use actix_web::{web, App, HttpServer};
-use clap::Parser;
-use sea_orm::sea_query::SqliteQueryBuilder;
-use sea_orm::{ConnectOptions, ConnectionTrait, Database, Schema, Statement};
+use clap::{Parser, Subcommand};
+use sea_orm::ActiveValue::Set;
+use sea_orm::{
+    ActiveEnum, ActiveModelTrait, ConnectOptions, Database, DatabaseConnection, EntityTrait,
+    PaginatorTrait,
+};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::OnceCell;
@@ -21,17 +33,268 @@ struct Args {
     #[arg(long, default_value = "sqlite://data.db?mode=rwc")]
     database_url: String,
 
-    #[arg(long, default_value = "")]
-    username: String,
+    /// Number of occurrences per error whose full details are always stored
+    #[arg(long, default_value_t = 100)]
+    sample_keep_first: u64,
 
-    #[arg(long, default_value = "")]
-    password: String,
+    /// After the first occurrences, store the details of one in every N (0 = none)
+    #[arg(long, default_value_t = 0)]
+    sample_every: u64,
 
-    #[arg(long, default_value = "")]
-    admin_account: String,
+    /// After the first occurrences, keep a reservoir of this many details per package and
+    /// version of each error (0 = disabled); with a reservoir, one in every N occurrences is
+    /// offered to it, where N is --sample-every (0 = every occurrence)
+    #[arg(long, default_value_t = 10)]
+    sample_reservoir: u64,
 
-    #[arg(long, default_value = "")]
-    admin_password: String,
+    /// Uploads per project per minute above which log bodies are dropped while still
+    /// counting occurrences (0 = disabled)
+    #[arg(long, default_value_t = 1000)]
+    spike_threshold: u64,
+
+    /// Seconds a project stays in spike mode after its upload rate was last above the threshold
+    #[arg(long, default_value_t = 300)]
+    spike_cooldown: u64,
+
+    /// How far in seconds a client timestamp may lag behind the receive time before it is
+    /// considered a broken clock and replaced by the receive time
+    #[arg(long, default_value_t = 30 * 24 * 3600)]
+    max_client_time_lag: i64,
+
+    /// Uploads allowed per client IP per minute (0 = unlimited)
+    #[arg(long, default_value_t = 600)]
+    ip_rate_limit: u32,
+
+    /// Burst size of the per-IP upload limit
+    #[arg(long, default_value_t = 120)]
+    ip_rate_burst: u32,
+
+    /// Uploads allowed per ingest key (or package when no key is sent) per minute (0 = unlimited)
+    #[arg(long, default_value_t = 0)]
+    key_rate_limit: u32,
+
+    /// Burst size of the per-key upload limit
+    #[arg(long, default_value_t = 1000)]
+    key_rate_burst: u32,
+
+    /// Rate limit by the client IP in Forwarded / X-Forwarded-For headers (only behind a trusted reverse proxy)
+    #[arg(long)]
+    trust_proxy: bool,
+
+    /// Capacity of the in-memory upload queue written by a background task (0 = write synchronously)
+    #[arg(long, default_value_t = 10000)]
+    ingest_queue_size: usize,
+
+    /// Interval in milliseconds at which queued uploads are flushed to the database
+    #[arg(long, default_value_t = 1000)]
+    ingest_flush_ms: u64,
+
+    /// Reject uploads that do not carry a valid project ingest key
+    #[arg(long)]
+    require_ingest_key: bool,
+
+    /// How to handle log types that match no rule: issue, event or drop
+    #[arg(long, default_value = "event")]
+    default_log_action: String,
+
+    /// How to compare versions of packages without a configured scheme: semver or build_number
+    #[arg(long, default_value = "semver")]
+    default_version_scheme: String,
+
+    /// Maximum size in bytes of a request body after gzip/deflate/br decompression
+    #[arg(long, default_value_t = 4 * 1024 * 1024)]
+    max_upload_size: usize,
+
+    /// Refuse to start when there are pending migrations instead of applying them
+    #[arg(long)]
+    no_auto_migrate: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Manage user accounts
+    Account {
+        #[command(subcommand)]
+        action: AccountAction,
+    },
+    /// Manage database schema migrations
+    Migrate {
+        #[command(subcommand)]
+        action: MigrateAction,
+    },
+    /// Recompute error hashes with the current grouping rules and merge collisions
+    Regroup {
+        /// Only report what would change
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum AccountAction {
+    /// Create an account
+    Create {
+        #[arg(long)]
+        username: String,
+        #[arg(long)]
+        password: String,
+        /// viewer, developer or admin
+        #[arg(long, default_value = "viewer")]
+        role: String,
+        /// Projects the account can access (comma separated ids, ignored for admin)
+        #[arg(long, value_delimiter = ',')]
+        projects: Vec<i32>,
+    },
+    /// Disable an account
+    Disable {
+        #[arg(long)]
+        username: String,
+    },
+    /// Re-enable a disabled account
+    Enable {
+        #[arg(long)]
+        username: String,
+    },
+    /// Reset the password of an account
+    ResetPassword {
+        #[arg(long)]
+        username: String,
+        #[arg(long)]
+        password: String,
+    },
+    /// List all accounts
+    List,
+}
+
+#[derive(Subcommand, Debug)]
+enum MigrateAction {
+    /// Apply pending migrations
+    Up {
+        /// Number of migrations to apply (default: all)
+        #[arg(short, long)]
+        num: Option<u32>,
+    },
+    /// Roll back applied migrations
+    Down {
+        /// Number of migrations to roll back
+        #[arg(short, long, default_value_t = 1)]
+        num: u32,
+    },
+    /// Show the status of every migration
+    Status,
+}
+
+async fn run_migrate(db: &DatabaseConnection, action: MigrateAction) -> anyhow::Result<()> {
+    match action {
+        MigrateAction::Up { num } => Migrator::up(db, num).await?,
+        MigrateAction::Down { num } => Migrator::down(db, Some(num)).await?,
+        MigrateAction::Status => {
+            for migration in Migrator::get_migration_with_status(db).await? {
+                println!("{:<8} {}", migration.status(), migration.name());
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn run_account(db: &DatabaseConnection, action: AccountAction) -> anyhow::Result<()> {
+    match action {
+        AccountAction::Create {
+            username,
+            password,
+            role,
+            projects,
+        } => {
+            let Some(role) = account::parse_role(&role) else {
+                anyhow::bail!("unknown role: {}", role);
+            };
+            if account::find_account(db, &username).await?.is_some() {
+                anyhow::bail!("account {} already exists", username);
+            }
+            let password_hash = account::hash_password(&password)?;
+            account::create_account(db, &username, password_hash, role, &projects).await?;
+            println!("Created account {}", username);
+        }
+        AccountAction::Disable { username } => set_account_disabled(db, &username, true).await?,
+        AccountAction::Enable { username } => set_account_disabled(db, &username, false).await?,
+        AccountAction::ResetPassword { username, password } => {
+            let Some(model) = account::find_account(db, &username).await? else {
+                anyhow::bail!("account {} not found", username);
+            };
+            let mut model: orm_entities::account::ActiveModel = model.into();
+            model.password_hash = Set(account::hash_password(&password)?);
+            model.update(db).await?;
+            println!("Password of {} has been reset", username);
+        }
+        AccountAction::List => {
+            let accounts = Account::find()
+                .find_with_related(AccountProject)
+                .all(db)
+                .await?;
+            for (model, projects) in accounts {
+                let projects: Vec<_> = projects.iter().map(|x| x.project_id.to_string()).collect();
+                println!(
+                    "{:<20} {:<10} {:<9} projects: {}",
+                    model.username,
+                    model.role.to_value(),
+                    if model.disabled {
+                        "disabled"
+                    } else {
+                        "enabled"
+                    },
+                    projects.join(",")
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn run_regroup(
+    db: &DatabaseConnection,
+    fingerprinter: &Fingerprinter,
+    versions: &VersionSchemes,
+    dry_run: bool,
+) -> anyhow::Result<()> {
+    let report = regroup::regroup(db, fingerprinter, versions, dry_run).await?;
+    for change in &report.changes {
+        println!(
+            "#{:<8} {} -> {} merged: {:?} total: {}",
+            change.log_id, change.old_hash, change.new_hash, change.merged_ids, change.total_count
+        );
+    }
+    println!(
+        "{} of {} errors {}",
+        report.changes.len(),
+        report.scanned,
+        if dry_run { "would change" } else { "changed" }
+    );
+    Ok(())
+}
+
+async fn set_account_disabled(
+    db: &DatabaseConnection,
+    username: &str,
+    disabled: bool,
+) -> anyhow::Result<()> {
+    let Some(model) = account::find_account(db, username).await? else {
+        anyhow::bail!("account {} not found", username);
+    };
+    if disabled && account::is_last_admin(db, &model).await? {
+        anyhow::bail!("cannot disable {}, the last enabled admin", username);
+    }
+    let mut model: orm_entities::account::ActiveModel = model.into();
+    model.disabled = Set(disabled);
+    model.update(db).await?;
+    println!(
+        "Account {} {}",
+        username,
+        if disabled { "disabled" } else { "enabled" }
+    );
+    Ok(())
 }
 
 #[actix_web::main]
@@ -52,65 +315,173 @@ async fn main() -> anyhow::Result<()> {
         .await
         .expect("Database initialization failed");
 
-    let backend = db_pool.get_database_backend();
-    let schema = Schema::new(backend);
-    db_pool
-        .execute(Statement::from_string(
-            backend,
-            schema
-                .create_table_from_entity(upload_user::Entity)
-                .if_not_exists()
-                .to_string(SqliteQueryBuilder),
-        ))
-        .await?;
-    db_pool
-        .execute(Statement::from_string(
-            backend,
-            schema
-                .create_table_from_entity(upload_log::Entity)
-                .if_not_exists()
-                .to_string(SqliteQueryBuilder),
-        ))
-        .await?;
-    db_pool
-        .execute(Statement::from_string(
-            backend,
-            schema
-                .create_table_from_entity(upload_statistics_cli_cfg::Entity)
-                .if_not_exists()
-                .to_string(SqliteQueryBuilder),
-        ))
-        .await?;
+    if let Some(Command::Migrate { action }) = args.command {
+        return run_migrate(&db_pool, action).await;
+    }
+
+    // 启动时检查未执行的迁移
+    let pending = Migrator::get_pending_migrations(&db_pool).await?;
+    if !pending.is_empty() {
+        let names: Vec<_> = pending.iter().map(|x| x.name().to_string()).collect();
+        if args.no_auto_migrate {
+            anyhow::bail!(
+                "{} pending migration(s): {}, run `migrate up` first",
+                names.len(),
+                names.join(", ")
+            );
+        }
+        println!("Applying migrations: {}", names.join(", "));
+        Migrator::up(&db_pool, None).await?;
+    }
+
+    if let Some(Command::Account { action }) = args.command {
+        return run_account(&db_pool, action).await;
+    }
+
+    let fingerprinter = Fingerprinter::default();
+    fingerprinter.reload_rules(&db_pool).await?;
+
+    let Ok(default_version_scheme) = VersionScheme::try_from_value(&args.default_version_scheme)
+    else {
+        anyhow::bail!("unknown version scheme: {}", args.default_version_scheme);
+    };
+    let version_schemes = Arc::new(VersionSchemes::new(default_version_scheme));
+    version_schemes.reload(&db_pool).await?;
+
+    if let Some(Command::Regroup { dry_run }) = args.command {
+        return run_regroup(&db_pool, &fingerprinter, &version_schemes, dry_run).await;
+    }
+
+    let Ok(default_log_action) = LogAction::try_from_value(&args.default_log_action) else {
+        anyhow::bail!("unknown log action: {}", args.default_log_action);
+    };
+    let log_type_rules = LogTypeRules::new(default_log_action);
+    log_type_rules.reload(&db_pool).await?;
+
+    let sampling = SamplingPolicy {
+        keep_first: args.sample_keep_first,
+        sample_every: args.sample_every,
+        reservoir: args.sample_reservoir,
+    };
+    let (ingest_queue, ingest_writer) = if args.ingest_queue_size > 0 {
+        let (queue, writer) = IngestQueue::start(
+            db_pool.clone(),
+            sampling,
+            version_schemes.clone(),
+            args.ingest_queue_size,
+            Duration::from_millis(args.ingest_flush_ms),
+        );
+        (Some(queue), Some(writer))
+    } else {
+        (None, None)
+    };
+
+    if Account::find().count(&db_pool).await? == 0 {
+        println!(
+            "No account has been created, the dashboard is read-only until an admin is created with `account create --role admin`"
+        );
+    }
 
     println!("Starting server at http://{}", args.listen_addr);
 
     let app_state = AppState {
-        username: Arc::new(args.username.to_owned()),
-        password: Arc::new(args.password.to_owned()),
-        admin_account: Arc::new(args.admin_account.to_owned()),
-        admin_password: Arc::new(args.admin_password.to_owned()),
         db_pool: Arc::new(OnceCell::const_new_with(db_pool)),
+        sampling,
+        clock: ClockPolicy {
+            max_lag: args.max_client_time_lag,
+        },
+        require_ingest_key: args.require_ingest_key,
+        auth_cache: Default::default(),
+        fingerprinter: Arc::new(fingerprinter),
+        log_type_rules: Arc::new(log_type_rules),
+        rate_limiter: Arc::new(RateLimiter::new(
+            RateLimit {
+                per_minute: args.ip_rate_limit,
+                burst: args.ip_rate_burst,
+            },
+            RateLimit {
+                per_minute: args.key_rate_limit,
+                burst: args.key_rate_burst,
+            },
+            args.trust_proxy,
+        )),
+        spike_detector: Arc::new(SpikeDetector::new(
+            args.spike_threshold,
+            Duration::from_secs(args.spike_cooldown),
+        )),
+        version_schemes,
+        ingest_queue,
     };
 
+    let max_upload_size = args.max_upload_size;
     HttpServer::new(move || {
         App::new()
             .app_data(web::Data::new(app_state.clone()))
+            .app_data(api::json_config(max_upload_size))
+            .app_data(web::PayloadConfig::new(max_upload_size))
+            .service(api::log::api_upload_log_batch)
             .service(api::log::api_upload_log)
+            .service(api::sentry::api_sentry_store)
+            .service(api::sentry::api_sentry_envelope)
+            .service(api::otlp::api_otlp_logs)
             .service(api::log::api_log_list)
             .service(api::log::api_log_content)
             .service(api::log::api_user_log)
             .service(api::log::api_log_complete)
             .service(api::log::api_log_remove)
+            .service(api::issue_status::api_log_status)
+            .service(api::issue_status::api_log_status_history)
+            .service(api::version::api_version_scheme_list)
+            .service(api::version::api_version_scheme_save)
+            .service(api::version::api_version_scheme_remove)
+            .service(api::release::api_release_list)
             .service(api::log::api_clear_log)
+            .service(api::log_merge::api_log_merge)
+            .service(api::log_merge::api_log_unmerge)
+            .service(api::log_merge::api_log_split)
             .service(api::log_html::log_content)
+            .service(api::log_html::events)
             .service(api::statistics::api_upload_statistics)
+            .service(api::statistics::api_statistics_daily)
+            .service(api::statistics::api_statistics_capabilities)
+            .service(api::statistics::api_statistics_devices)
+            .service(api::statistics_device::api_statistics_active)
+            .service(api::statistics_device::api_statistics_retention)
             .service(api::statistics_html::statistics_users)
+            .service(api::statistics_html::statistics_capabilities)
             .service(api::query_ip::api_query_ip_json)
+            .service(api::project::api_project_list)
+            .service(api::project::api_project_create)
+            .service(api::project::api_project_key_rotate)
+            .service(api::project::api_project_key_revoke)
+            .service(api::project_html::projects)
+            .service(api::grouping::api_normalize_rule_list)
+            .service(api::grouping::api_normalize_rule_create)
+            .service(api::grouping::api_normalize_rule_update)
+            .service(api::grouping::api_normalize_rule_remove)
+            .service(api::grouping::api_grouping_preview)
+            .service(api::log_type::api_log_type_rule_list)
+            .service(api::log_type::api_log_type_rule_save)
+            .service(api::log_type::api_log_type_rule_remove)
+            .service(api::log_type::api_event_list)
+            .service(api::regroup::api_regroup)
+            .service(api::rate_limit::api_rate_limit_stats)
+            .service(api::spike::api_spike_status)
+            .service(api::ingest_queue::api_ingest_queue_stats)
+            .service(api::grouping_html::grouping)
+            .service(api::account::api_account_list)
+            .service(api::account::api_account_create)
+            .service(api::account::api_account_update)
             .service(api::log_html::index)
     })
     .bind(&args.listen_addr)?
     .run()
     .await?;
 
+    // 服务停止后等待队列中剩余的上报写入
+    if let Some(writer) = ingest_writer {
+        writer.await?;
+    }
+
     Ok(())
 }
@@ -0,0 +1,157 @@
+use sea_orm_migration::prelude::*;
+
+/// 初始表结构, 对已有数据库是空操作
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(UploadUser::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(UploadUser::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(&mut tiny_text(UploadUser::Package))
+                    .col(&mut tiny_text(UploadUser::NavUrl))
+                    .col(ColumnDef::new(UploadUser::Version).text().not_null())
+                    .col(ColumnDef::new(UploadUser::Logs).text().not_null())
+                    .col(&mut tiny_text(UploadUser::User))
+                    .col(&mut tiny_text(UploadUser::Ip))
+                    .col(ColumnDef::new(UploadUser::Time).date_time().not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(UploadLog::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(UploadLog::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(&mut tiny_text(UploadLog::Hash))
+                    .col(ColumnDef::new(UploadLog::UserList).text().not_null())
+                    .col(ColumnDef::new(UploadLog::FirstTime).date_time().not_null())
+                    .col(ColumnDef::new(UploadLog::LastTime).date_time().not_null())
+                    .col(ColumnDef::new(UploadLog::TotalCount).integer().not_null())
+                    .col(ColumnDef::new(UploadLog::Status).integer().not_null())
+                    .col(
+                        ColumnDef::new(UploadLog::ResolutionTime)
+                            .date_time()
+                            .not_null(),
+                    )
+                    .col(&mut tiny_text(UploadLog::LogType))
+                    .col(ColumnDef::new(UploadLog::Message).text().not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(UploadStatisticsCliCfg::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(UploadStatisticsCliCfg::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(&mut tiny_text(UploadStatisticsCliCfg::CliType))
+                    .col(&mut tiny_text(UploadStatisticsCliCfg::User))
+                    .col(&mut tiny_text(UploadStatisticsCliCfg::Package))
+                    .col(
+                        ColumnDef::new(UploadStatisticsCliCfg::ConfigurationInfo)
+                            .text()
+                            .not_null(),
+                    )
+                    .col(&mut tiny_text(UploadStatisticsCliCfg::Ip))
+                    .col(&mut tiny_text(UploadStatisticsCliCfg::Region))
+                    .col(
+                        ColumnDef::new(UploadStatisticsCliCfg::Time)
+                            .date_time()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(
+                Table::drop()
+                    .table(UploadStatisticsCliCfg::Table)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .drop_table(Table::drop().table(UploadLog::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(UploadUser::Table).to_owned())
+            .await
+    }
+}
+
+fn tiny_text<T: IntoIden>(name: T) -> ColumnDef {
+    ColumnDef::new(name)
+        .custom(Alias::new("TINYTEXT"))
+        .not_null()
+        .to_owned()
+}
+
+#[derive(DeriveIden)]
+enum UploadUser {
+    Table,
+    Id,
+    Package,
+    NavUrl,
+    Version,
+    Logs,
+    User,
+    Ip,
+    Time,
+}
+
+#[derive(DeriveIden)]
+enum UploadLog {
+    Table,
+    Id,
+    Hash,
+    UserList,
+    FirstTime,
+    LastTime,
+    TotalCount,
+    Status,
+    ResolutionTime,
+    LogType,
+    Message,
+}
+
+#[derive(DeriveIden)]
+enum UploadStatisticsCliCfg {
+    Table,
+    Id,
+    CliType,
+    User,
+    Package,
+    ConfigurationInfo,
+    Ip,
+    Region,
+    Time,
+}
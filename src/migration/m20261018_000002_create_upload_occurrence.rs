@@ -0,0 +1,132 @@
+use sea_orm_migration::prelude::*;
+
+/// 新增 upload_occurrence 表, 并迁移旧版 `upload_log.user_list` 中的用户id
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(UploadOccurrence::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(UploadOccurrence::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(UploadOccurrence::LogId).integer().not_null())
+                    .col(
+                        ColumnDef::new(UploadOccurrence::UserId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(UploadOccurrence::Time)
+                            .date_time()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(UploadOccurrence::Table, UploadOccurrence::LogId)
+                            .to(UploadLog::Table, UploadLog::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(UploadOccurrence::Table, UploadOccurrence::UserId)
+                            .to(UploadUser::Table, UploadUser::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx-upload_occurrence-log_id")
+                    .table(UploadOccurrence::Table)
+                    .col(UploadOccurrence::LogId)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx-upload_occurrence-user_id")
+                    .table(UploadOccurrence::Table)
+                    .col(UploadOccurrence::UserId)
+                    .to_owned(),
+            )
+            .await?;
+
+        // 拆分逗号分隔的 user_list, 每个仍存在的用户生成一条 occurrence
+        let db = manager.get_connection();
+        db.execute_unprepared(
+            "WITH RECURSIVE split(log_id, user_id, rest) AS (
+                SELECT id, '', user_list || ',' FROM upload_log WHERE user_list != ''
+                UNION ALL
+                SELECT log_id, substr(rest, 1, instr(rest, ',') - 1), substr(rest, instr(rest, ',') + 1)
+                FROM split WHERE rest != ''
+            )
+            INSERT INTO upload_occurrence (log_id, user_id, time)
+            SELECT split.log_id, upload_user.id, upload_user.time
+            FROM split JOIN upload_user ON upload_user.id = CAST(split.user_id AS INTEGER)
+            WHERE split.user_id != ''
+            ORDER BY upload_user.id",
+        )
+        .await?;
+        db.execute_unprepared("UPDATE upload_log SET user_list = '' WHERE user_list != ''")
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // 还原为逗号分隔的 user_list
+        manager
+            .get_connection()
+            .execute_unprepared(
+                "UPDATE upload_log SET user_list = COALESCE((
+                    SELECT group_concat(user_id, ',') FROM (
+                        SELECT user_id FROM upload_occurrence
+                        WHERE upload_occurrence.log_id = upload_log.id
+                        ORDER BY id
+                    )
+                ), '')",
+            )
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(UploadOccurrence::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum UploadOccurrence {
+    Table,
+    Id,
+    LogId,
+    UserId,
+    Time,
+}
+
+#[derive(DeriveIden)]
+enum UploadLog {
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum UploadUser {
+    Table,
+    Id,
+}
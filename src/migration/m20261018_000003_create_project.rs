@@ -0,0 +1,149 @@
+use sea_orm_migration::prelude::*;
+
+/// 新增项目及上报密钥表, 错误和统计数据关联到项目(0 表示未指定项目)
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Project::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Project::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(Project::Name)
+                            .custom(Alias::new("TINYTEXT"))
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(Project::CreateTime).date_time().not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(ProjectKey::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ProjectKey::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(ProjectKey::ProjectId).integer().not_null())
+                    .col(
+                        ColumnDef::new(ProjectKey::PublicKey)
+                            .custom(Alias::new("TINYTEXT"))
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(
+                        ColumnDef::new(ProjectKey::CreateTime)
+                            .date_time()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(ProjectKey::RevokeTime).date_time().null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(ProjectKey::Table, ProjectKey::ProjectId)
+                            .to(Project::Table, Project::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(UploadLog::Table)
+                    .add_column(
+                        ColumnDef::new(UploadLog::ProjectId)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(UploadStatisticsCliCfg::Table)
+                    .add_column(
+                        ColumnDef::new(UploadStatisticsCliCfg::ProjectId)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(UploadStatisticsCliCfg::Table)
+                    .drop_column(UploadStatisticsCliCfg::ProjectId)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(UploadLog::Table)
+                    .drop_column(UploadLog::ProjectId)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .drop_table(Table::drop().table(ProjectKey::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(Project::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Project {
+    Table,
+    Id,
+    Name,
+    CreateTime,
+}
+
+#[derive(DeriveIden)]
+enum ProjectKey {
+    Table,
+    Id,
+    ProjectId,
+    PublicKey,
+    CreateTime,
+    RevokeTime,
+}
+
+#[derive(DeriveIden)]
+enum UploadLog {
+    Table,
+    ProjectId,
+}
+
+#[derive(DeriveIden)]
+enum UploadStatisticsCliCfg {
+    Table,
+    ProjectId,
+}
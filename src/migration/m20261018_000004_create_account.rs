@@ -0,0 +1,124 @@
+use sea_orm_migration::prelude::*;
+
+/// 新增账号及账号项目权限表
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Account::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Account::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(Account::Username)
+                            .custom(Alias::new("TINYTEXT"))
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(ColumnDef::new(Account::PasswordHash).text().not_null())
+                    .col(ColumnDef::new(Account::Role).string().not_null())
+                    .col(
+                        ColumnDef::new(Account::Disabled)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .col(ColumnDef::new(Account::CreateTime).date_time().not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(AccountProject::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(AccountProject::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(AccountProject::AccountId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(AccountProject::ProjectId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(AccountProject::Table, AccountProject::AccountId)
+                            .to(Account::Table, Account::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(AccountProject::Table, AccountProject::ProjectId)
+                            .to(Project::Table, Project::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx-account_project-account_id")
+                    .table(AccountProject::Table)
+                    .col(AccountProject::AccountId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(AccountProject::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(Account::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Account {
+    Table,
+    Id,
+    Username,
+    PasswordHash,
+    Role,
+    Disabled,
+    CreateTime,
+}
+
+#[derive(DeriveIden)]
+enum AccountProject {
+    Table,
+    Id,
+    AccountId,
+    ProjectId,
+}
+
+#[derive(DeriveIden)]
+enum Project {
+    Table,
+    Id,
+}
@@ -0,0 +1,109 @@
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::{FromQueryResult, Statement};
+use serde_json::{Map, Value};
+
+/// 新增 `configuration_json` 列保存解析后的客户端配置, 并解析已有数据
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(FromQueryResult)]
+struct ConfigurationRow {
+    id: i32,
+    configuration_info: String,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(UploadStatisticsCliCfg::Table)
+                    .add_column(
+                        ColumnDef::new(UploadStatisticsCliCfg::ConfigurationJson)
+                            .json()
+                            .not_null()
+                            .default("{}"),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        let db = manager.get_connection();
+        let backend = manager.get_database_backend();
+        let rows = ConfigurationRow::find_by_statement(Statement::from_string(
+            backend,
+            "SELECT id, configuration_info FROM upload_statistics_cli_cfg WHERE configuration_info != ''",
+        ))
+        .all(db)
+        .await?;
+
+        for row in rows {
+            let info = parse_configuration_info(&row.configuration_info);
+            db.execute(Statement::from_sql_and_values(
+                backend,
+                "UPDATE upload_statistics_cli_cfg SET configuration_json = ? WHERE id = ?",
+                [Value::Object(info).to_string().into(), row.id.into()],
+            ))
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(UploadStatisticsCliCfg::Table)
+                    .drop_column(UploadStatisticsCliCfg::ConfigurationJson)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+/// 迁移时的配置信息解析规则, 独立于 `api::configuration_info` 保存,
+/// 之后修改运行时的解析规则不影响该迁移的结果
+fn parse_configuration_info(info: &str) -> Map<String, Value> {
+    if let Ok(Value::Object(result)) = serde_json::from_str(info) {
+        return result;
+    }
+
+    let mut result = Map::new();
+
+    let cleaned = info.trim_matches(|c: char| c == '{' || c == '}' || c.is_whitespace());
+    for item in cleaned.split([',', '\n']) {
+        let Some((key, value)) = item.split_once(':') else {
+            continue;
+        };
+        let key = key.trim();
+        if key.is_empty()
+            || !key
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '_' || c == '.')
+        {
+            continue;
+        }
+
+        let value = value.trim();
+        let value = if value.eq_ignore_ascii_case("true") {
+            Value::Bool(true)
+        } else if value.eq_ignore_ascii_case("false") {
+            Value::Bool(false)
+        } else if let Ok(x) = value.parse::<u64>() {
+            Value::from(x)
+        } else {
+            Value::String(value.to_string())
+        };
+        result.insert(key.to_string(), value);
+    }
+
+    result
+}
+
+#[derive(DeriveIden)]
+enum UploadStatisticsCliCfg {
+    Table,
+    ConfigurationJson,
+}
@@ -0,0 +1,218 @@
+use sea_orm_migration::prelude::*;
+
+/// 新增设备表及设备活跃日期表, 并根据已有的上报记录生成设备数据
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(UploadStatisticsCliCfg::Table)
+                    .add_column(
+                        ColumnDef::new(UploadStatisticsCliCfg::DeviceId)
+                            .custom(Alias::new("TINYTEXT"))
+                            .not_null()
+                            .default(""),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(StatisticsDevice::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(StatisticsDevice::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(StatisticsDevice::ProjectId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(&mut tiny_text(StatisticsDevice::Package))
+                    .col(&mut tiny_text(StatisticsDevice::User))
+                    .col(&mut tiny_text(StatisticsDevice::DeviceId))
+                    .col(&mut tiny_text(StatisticsDevice::CliType))
+                    .col(&mut tiny_text(StatisticsDevice::Region))
+                    .col(
+                        ColumnDef::new(StatisticsDevice::FirstSeen)
+                            .date_time()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(StatisticsDevice::LastSeen)
+                            .date_time()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(StatisticsDevice::LaunchCount)
+                            .integer()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx-statistics_device-identity")
+                    .table(StatisticsDevice::Table)
+                    .col(StatisticsDevice::ProjectId)
+                    .col(StatisticsDevice::Package)
+                    .col(StatisticsDevice::User)
+                    .col(StatisticsDevice::DeviceId)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx-statistics_device-first_seen")
+                    .table(StatisticsDevice::Table)
+                    .col(StatisticsDevice::FirstSeen)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(StatisticsDeviceDay::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(StatisticsDeviceDay::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(StatisticsDeviceDay::DeviceId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(StatisticsDeviceDay::Date).date().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(StatisticsDeviceDay::Table, StatisticsDeviceDay::DeviceId)
+                            .to(StatisticsDevice::Table, StatisticsDevice::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx-statistics_device_day-device_id-date")
+                    .table(StatisticsDeviceDay::Table)
+                    .col(StatisticsDeviceDay::DeviceId)
+                    .col(StatisticsDeviceDay::Date)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx-statistics_device_day-date")
+                    .table(StatisticsDeviceDay::Table)
+                    .col(StatisticsDeviceDay::Date)
+                    .to_owned(),
+            )
+            .await?;
+
+        // 旧数据没有设备id, 按 项目 + 包名 + 用户 归并为设备
+        let db = manager.get_connection();
+        db.execute_unprepared(
+            "INSERT INTO statistics_device
+                (project_id, package, user, device_id, cli_type, region, first_seen, last_seen, launch_count)
+            SELECT project_id, package, user, '', MAX(cli_type), MAX(region), MIN(time), MAX(time), COUNT(*)
+            FROM upload_statistics_cli_cfg
+            GROUP BY project_id, package, user",
+        )
+        .await?;
+        db.execute_unprepared(
+            "INSERT INTO statistics_device_day (device_id, date)
+            SELECT DISTINCT statistics_device.id, DATE(upload_statistics_cli_cfg.time)
+            FROM upload_statistics_cli_cfg
+            JOIN statistics_device
+                ON statistics_device.project_id = upload_statistics_cli_cfg.project_id
+                AND statistics_device.package = upload_statistics_cli_cfg.package
+                AND statistics_device.user = upload_statistics_cli_cfg.user
+                AND statistics_device.device_id = ''",
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(StatisticsDeviceDay::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(StatisticsDevice::Table).to_owned())
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(UploadStatisticsCliCfg::Table)
+                    .drop_column(UploadStatisticsCliCfg::DeviceId)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+fn tiny_text<T: IntoIden>(name: T) -> ColumnDef {
+    ColumnDef::new(name)
+        .custom(Alias::new("TINYTEXT"))
+        .not_null()
+        .to_owned()
+}
+
+#[derive(DeriveIden)]
+enum UploadStatisticsCliCfg {
+    Table,
+    DeviceId,
+}
+
+#[derive(DeriveIden)]
+enum StatisticsDevice {
+    Table,
+    Id,
+    ProjectId,
+    Package,
+    User,
+    DeviceId,
+    CliType,
+    Region,
+    FirstSeen,
+    LastSeen,
+    LaunchCount,
+}
+
+#[derive(DeriveIden)]
+enum StatisticsDeviceDay {
+    Table,
+    Id,
+    DeviceId,
+    Date,
+}
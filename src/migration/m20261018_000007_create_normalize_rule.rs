@@ -0,0 +1,110 @@
+use sea_orm_migration::prelude::*;
+
+/// 新增错误消息归一化规则表
+///
+/// 项目 0 的规则对所有项目生效; 内存地址规则默认启用以保持已有的分组哈希不变,
+/// 其余常用规则默认关闭, 由管理员按需启用
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+const DEFAULT_RULES: [(&str, &str, bool); 6] = [
+    (r"0x[0-9a-fA-F]+", "[MEMORY_ADDRESS]", true),
+    (
+        r"[0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12}",
+        "[UUID]",
+        false,
+    ),
+    (r#"[a-zA-Z][a-zA-Z0-9+.\-]*://[^\s'"]+"#, "[URL]", false),
+    (
+        r#"(?:/tmp/|/var/folders/|[A-Za-z]:\\Users\\[^\\]+\\AppData\\Local\\Temp\\)[^\s'"]*"#,
+        "[TEMP_PATH]",
+        false,
+    ),
+    (
+        r"(?i)\b(uid|user_?id|player_?id)([=: ]+)\w+",
+        "${1}${2}[USER_ID]",
+        false,
+    ),
+    (r"\b\d+\b", "[NUMBER]", false),
+];
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(NormalizeRule::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(NormalizeRule::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(NormalizeRule::ProjectId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(NormalizeRule::Position).integer().not_null())
+                    .col(ColumnDef::new(NormalizeRule::Pattern).text().not_null())
+                    .col(
+                        ColumnDef::new(NormalizeRule::Replacement)
+                            .custom(Alias::new("TINYTEXT"))
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(NormalizeRule::Enabled).boolean().not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-normalize_rule-project_id-position")
+                    .table(NormalizeRule::Table)
+                    .col(NormalizeRule::ProjectId)
+                    .col(NormalizeRule::Position)
+                    .to_owned(),
+            )
+            .await?;
+
+        let mut insert = Query::insert();
+        insert.into_table(NormalizeRule::Table).columns([
+            NormalizeRule::ProjectId,
+            NormalizeRule::Position,
+            NormalizeRule::Pattern,
+            NormalizeRule::Replacement,
+            NormalizeRule::Enabled,
+        ]);
+        for (position, (pattern, replacement, enabled)) in DEFAULT_RULES.into_iter().enumerate() {
+            insert.values_panic([
+                0.into(),
+                (position as i32).into(),
+                pattern.into(),
+                replacement.into(),
+                enabled.into(),
+            ]);
+        }
+        manager.exec_stmt(insert).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(NormalizeRule::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum NormalizeRule {
+    Table,
+    Id,
+    ProjectId,
+    Position,
+    Pattern,
+    Replacement,
+    Enabled,
+}
@@ -0,0 +1,40 @@
+use sea_orm_migration::prelude::*;
+
+/// 保存上报时指定的分组指纹, 重新分组时使用
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(UploadLog::Table)
+                    .add_column(
+                        ColumnDef::new(UploadLog::Fingerprint)
+                            .custom(Alias::new("TINYTEXT"))
+                            .null(),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(UploadLog::Table)
+                    .drop_column(UploadLog::Fingerprint)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum UploadLog {
+    Table,
+    Fingerprint,
+}
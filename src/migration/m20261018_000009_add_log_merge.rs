@@ -0,0 +1,110 @@
+use sea_orm_migration::prelude::*;
+
+/// 支持手动合并错误: 记录错误合并到的主错误, 以及每条上报详情原始的哈希值
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(UploadLog::Table)
+                    .add_column(ColumnDef::new(UploadLog::MergedInto).integer().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-upload_log-merged_into")
+                    .table(UploadLog::Table)
+                    .col(UploadLog::MergedInto)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(UploadOccurrence::Table)
+                    .add_column(
+                        ColumnDef::new(UploadOccurrence::Hash)
+                            .custom(Alias::new("TINYTEXT"))
+                            .not_null()
+                            .default(""),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        let db = manager.get_connection();
+        db.execute_unprepared(
+            "UPDATE upload_occurrence SET hash = \
+             (SELECT hash FROM upload_log WHERE upload_log.id = upload_occurrence.log_id)",
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // 取消所有合并, 上报详情归还到原错误
+        let db = manager.get_connection();
+        db.execute_unprepared(
+            "UPDATE upload_occurrence SET log_id = \
+             (SELECT id FROM upload_log WHERE upload_log.hash = upload_occurrence.hash \
+             ORDER BY merged_into IS NOT NULL DESC LIMIT 1) \
+             WHERE log_id IN (SELECT merged_into FROM upload_log WHERE merged_into IS NOT NULL) \
+             AND hash IN (SELECT hash FROM upload_log WHERE merged_into IS NOT NULL)",
+        )
+        .await?;
+        db.execute_unprepared(
+            "UPDATE upload_log SET total_count = MAX(0, total_count - \
+             (SELECT COALESCE(SUM(merged.total_count), 0) FROM upload_log AS merged \
+             WHERE merged.merged_into = upload_log.id))",
+        )
+        .await?;
+
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx-upload_log-merged_into")
+                    .table(UploadLog::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(UploadLog::Table)
+                    .drop_column(UploadLog::MergedInto)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(UploadOccurrence::Table)
+                    .drop_column(UploadOccurrence::Hash)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum UploadLog {
+    Table,
+    MergedInto,
+}
+
+#[derive(DeriveIden)]
+enum UploadOccurrence {
+    Table,
+    Hash,
+}
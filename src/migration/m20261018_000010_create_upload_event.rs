@@ -0,0 +1,136 @@
+use sea_orm_migration::prelude::*;
+
+/// 新增按 log_type 配置处理方式的规则表及原始事件表
+///
+/// 默认规则保持原有行为: `error` 开头的上报按错误分组保存
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(LogTypeRule::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(LogTypeRule::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(LogTypeRule::ProjectId).integer().not_null())
+                    .col(&mut tiny_text(LogTypeRule::LogType))
+                    .col(ColumnDef::new(LogTypeRule::Action).string().not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-log_type_rule-project_id-log_type")
+                    .table(LogTypeRule::Table)
+                    .col(LogTypeRule::ProjectId)
+                    .col(LogTypeRule::LogType)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .exec_stmt(
+                Query::insert()
+                    .into_table(LogTypeRule::Table)
+                    .columns([
+                        LogTypeRule::ProjectId,
+                        LogTypeRule::LogType,
+                        LogTypeRule::Action,
+                    ])
+                    .values_panic([0.into(), "error".into(), "issue".into()])
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(UploadEvent::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(UploadEvent::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(UploadEvent::ProjectId).integer().not_null())
+                    .col(&mut tiny_text(UploadEvent::LogType))
+                    .col(ColumnDef::new(UploadEvent::Message).text().not_null())
+                    .col(&mut tiny_text(UploadEvent::User))
+                    .col(&mut tiny_text(UploadEvent::Package))
+                    .col(&mut tiny_text(UploadEvent::NavUrl))
+                    .col(&mut tiny_text(UploadEvent::Version))
+                    .col(ColumnDef::new(UploadEvent::Logs).text().not_null())
+                    .col(&mut tiny_text(UploadEvent::Ip))
+                    .col(ColumnDef::new(UploadEvent::Time).date_time().not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-upload_event-project_id-log_type-time")
+                    .table(UploadEvent::Table)
+                    .col(UploadEvent::ProjectId)
+                    .col(UploadEvent::LogType)
+                    .col(UploadEvent::Time)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(UploadEvent::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(LogTypeRule::Table).to_owned())
+            .await
+    }
+}
+
+fn tiny_text<T: IntoIden>(name: T) -> ColumnDef {
+    ColumnDef::new(name)
+        .custom(Alias::new("TINYTEXT"))
+        .not_null()
+        .to_owned()
+}
+
+#[derive(DeriveIden)]
+enum LogTypeRule {
+    Table,
+    Id,
+    ProjectId,
+    LogType,
+    Action,
+}
+
+#[derive(DeriveIden)]
+enum UploadEvent {
+    Table,
+    Id,
+    ProjectId,
+    LogType,
+    Message,
+    User,
+    Package,
+    NavUrl,
+    Version,
+    Logs,
+    Ip,
+    Time,
+}
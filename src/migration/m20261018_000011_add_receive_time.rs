@@ -0,0 +1,79 @@
+use sea_orm_migration::prelude::*;
+
+/// 上报时间改为客户端时间, 另外保存服务器接收时间及客户端时钟偏差
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+fn tables() -> [Alias; 3] {
+    [
+        Alias::new("upload_user"),
+        Alias::new("upload_event"),
+        Alias::new("upload_statistics_cli_cfg"),
+    ]
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        for table in tables() {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(table.clone())
+                        .add_column(
+                            ColumnDef::new(ReportTime::ReceiveTime)
+                                .date_time()
+                                .not_null()
+                                .default("1970-01-01 00:00:00"),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(table.clone())
+                        .add_column(ColumnDef::new(ReportTime::ClockSkew).big_integer().null())
+                        .to_owned(),
+                )
+                .await?;
+
+            // 已有数据的时间即为接收时间
+            db.execute_unprepared(&format!(
+                "UPDATE {} SET receive_time = time",
+                table.to_string()
+            ))
+            .await?;
+        }
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for table in tables() {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(table.clone())
+                        .drop_column(ReportTime::ClockSkew)
+                        .to_owned(),
+                )
+                .await?;
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(table)
+                        .drop_column(ReportTime::ReceiveTime)
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum ReportTime {
+    ReceiveTime,
+    ClockSkew,
+}
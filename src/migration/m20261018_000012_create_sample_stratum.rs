@@ -0,0 +1,111 @@
+use sea_orm_migration::prelude::*;
+
+/// 错误上报详情的蓄水池采样: 按错误 + 包名 + 版本分层记录上报次数, 并标记蓄水池中的上报详情
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(SampleStratum::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(SampleStratum::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(SampleStratum::LogId).integer().not_null())
+                    .col(&mut tiny_text(SampleStratum::Package))
+                    .col(&mut tiny_text(SampleStratum::Version))
+                    .col(
+                        ColumnDef::new(SampleStratum::Seen)
+                            .big_integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(SampleStratum::Table, SampleStratum::LogId)
+                            .to(UploadLog::Table, UploadLog::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-sample_stratum-log_id-package-version")
+                    .table(SampleStratum::Table)
+                    .col(SampleStratum::LogId)
+                    .col(SampleStratum::Package)
+                    .col(SampleStratum::Version)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(UploadOccurrence::Table)
+                    .add_column(
+                        ColumnDef::new(UploadOccurrence::Sampled)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(UploadOccurrence::Table)
+                    .drop_column(UploadOccurrence::Sampled)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .drop_table(Table::drop().table(SampleStratum::Table).to_owned())
+            .await
+    }
+}
+
+fn tiny_text<T: IntoIden>(name: T) -> ColumnDef {
+    ColumnDef::new(name)
+        .custom(Alias::new("TINYTEXT"))
+        .not_null()
+        .to_owned()
+}
+
+#[derive(DeriveIden)]
+enum SampleStratum {
+    Table,
+    Id,
+    LogId,
+    Package,
+    Version,
+    Seen,
+}
+
+#[derive(DeriveIden)]
+enum UploadLog {
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum UploadOccurrence {
+    Table,
+    Sampled,
+}
@@ -0,0 +1,132 @@
+use sea_orm_migration::prelude::*;
+
+/// 错误状态扩展: 静音截止时间/次数, 下个版本解决时的版本号, 以及状态变更记录
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // SQLite 每条 ALTER TABLE 只能添加一列
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(UploadLog::Table)
+                    .add_column(ColumnDef::new(UploadLog::MutedUntil).date_time().null())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(UploadLog::Table)
+                    .add_column(ColumnDef::new(UploadLog::MutedUntilCount).integer().null())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(UploadLog::Table)
+                    .add_column(
+                        ColumnDef::new(UploadLog::ResolvedVersion)
+                            .custom(Alias::new("TINYTEXT"))
+                            .null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(LogStatusChange::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(LogStatusChange::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(LogStatusChange::LogId).integer().not_null())
+                    .col(
+                        ColumnDef::new(LogStatusChange::FromStatus)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(LogStatusChange::ToStatus)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(LogStatusChange::Reason).text().not_null())
+                    .col(
+                        ColumnDef::new(LogStatusChange::Operator)
+                            .custom(Alias::new("TINYTEXT"))
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(LogStatusChange::Time).date_time().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(LogStatusChange::Table, LogStatusChange::LogId)
+                            .to(UploadLog::Table, UploadLog::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-log_status_change-log_id")
+                    .table(LogStatusChange::Table)
+                    .col(LogStatusChange::LogId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(LogStatusChange::Table).to_owned())
+            .await?;
+        for column in [
+            UploadLog::MutedUntil,
+            UploadLog::MutedUntilCount,
+            UploadLog::ResolvedVersion,
+        ] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(UploadLog::Table)
+                        .drop_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum UploadLog {
+    Table,
+    Id,
+    MutedUntil,
+    MutedUntilCount,
+    ResolvedVersion,
+}
+
+#[derive(DeriveIden)]
+enum LogStatusChange {
+    Table,
+    Id,
+    LogId,
+    FromStatus,
+    ToStatus,
+    Reason,
+    Operator,
+    Time,
+}
@@ -0,0 +1,51 @@
+use sea_orm_migration::prelude::*;
+
+/// 按包名配置的版本号比较方式, 用于判断错误是否在已解决的版本之后复现
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(PackageVersionScheme::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(PackageVersionScheme::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(PackageVersionScheme::Package)
+                            .custom(Alias::new("TINYTEXT"))
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(
+                        ColumnDef::new(PackageVersionScheme::Scheme)
+                            .string()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(PackageVersionScheme::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum PackageVersionScheme {
+    Table,
+    Id,
+    Package,
+    Scheme,
+}
@@ -0,0 +1,230 @@
+use sea_orm_migration::prelude::*;
+
+/// 新增版本表及版本用户表, 客户端启动统计增加版本字段, 并根据已有的错误上报详情生成版本数据
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(UploadStatisticsCliCfg::Table)
+                    .add_column(
+                        ColumnDef::new(UploadStatisticsCliCfg::Version)
+                            .custom(Alias::new("TINYTEXT"))
+                            .not_null()
+                            .default(""),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(PackageRelease::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(PackageRelease::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(PackageRelease::ProjectId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(&mut tiny_text(PackageRelease::Package))
+                    .col(&mut tiny_text(PackageRelease::Version))
+                    .col(
+                        ColumnDef::new(PackageRelease::FirstSeen)
+                            .date_time()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(PackageRelease::LastSeen)
+                            .date_time()
+                            .not_null(),
+                    )
+                    .col(&mut counter(PackageRelease::LaunchCount))
+                    .col(&mut counter(PackageRelease::OccurrenceCount))
+                    .col(&mut counter(PackageRelease::NewIssueCount))
+                    .col(&mut counter(PackageRelease::RegressedIssueCount))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx-package_release-identity")
+                    .table(PackageRelease::Table)
+                    .col(PackageRelease::ProjectId)
+                    .col(PackageRelease::Package)
+                    .col(PackageRelease::Version)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx-package_release-first_seen")
+                    .table(PackageRelease::Table)
+                    .col(PackageRelease::FirstSeen)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(PackageReleaseUser::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(PackageReleaseUser::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(PackageReleaseUser::ReleaseId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(&mut tiny_text(PackageReleaseUser::User))
+                    .col(
+                        ColumnDef::new(PackageReleaseUser::Launched)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .col(
+                        ColumnDef::new(PackageReleaseUser::Errored)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(PackageReleaseUser::Table, PackageReleaseUser::ReleaseId)
+                            .to(PackageRelease::Table, PackageRelease::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx-package_release_user-release_id-user")
+                    .table(PackageReleaseUser::Table)
+                    .col(PackageReleaseUser::ReleaseId)
+                    .col(PackageReleaseUser::User)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        // 旧的启动统计没有版本, 只能根据保存的错误上报详情生成版本及出错用户
+        let db = manager.get_connection();
+        db.execute_unprepared(
+            "INSERT INTO package_release
+                (project_id, package, version, first_seen, last_seen, occurrence_count)
+            SELECT upload_log.project_id, upload_user.package, upload_user.version,
+                MIN(upload_user.time), MAX(upload_user.time), COUNT(*)
+            FROM upload_occurrence
+            INNER JOIN upload_user ON upload_user.id = upload_occurrence.user_id
+            INNER JOIN upload_log ON upload_log.id = upload_occurrence.log_id
+            WHERE upload_user.version != ''
+            GROUP BY upload_log.project_id, upload_user.package, upload_user.version",
+        )
+        .await?;
+        db.execute_unprepared(
+            "INSERT INTO package_release_user (release_id, user, launched, errored)
+            SELECT DISTINCT package_release.id, upload_user.user, FALSE, TRUE
+            FROM upload_occurrence
+            INNER JOIN upload_user ON upload_user.id = upload_occurrence.user_id
+            INNER JOIN upload_log ON upload_log.id = upload_occurrence.log_id
+            INNER JOIN package_release ON package_release.project_id = upload_log.project_id
+                AND package_release.package = upload_user.package
+                AND package_release.version = upload_user.version
+            WHERE upload_user.user != ''",
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(PackageReleaseUser::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(PackageRelease::Table).to_owned())
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(UploadStatisticsCliCfg::Table)
+                    .drop_column(UploadStatisticsCliCfg::Version)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+fn tiny_text<T: IntoIden>(name: T) -> ColumnDef {
+    ColumnDef::new(name)
+        .custom(Alias::new("TINYTEXT"))
+        .not_null()
+        .to_owned()
+}
+
+fn counter<T: IntoIden>(name: T) -> ColumnDef {
+    ColumnDef::new(name)
+        .integer()
+        .not_null()
+        .default(0)
+        .to_owned()
+}
+
+#[derive(DeriveIden)]
+enum UploadStatisticsCliCfg {
+    Table,
+    Version,
+}
+
+#[derive(DeriveIden)]
+enum PackageRelease {
+    Table,
+    Id,
+    ProjectId,
+    Package,
+    Version,
+    FirstSeen,
+    LastSeen,
+    LaunchCount,
+    OccurrenceCount,
+    NewIssueCount,
+    RegressedIssueCount,
+}
+
+#[derive(DeriveIden)]
+enum PackageReleaseUser {
+    Table,
+    Id,
+    ReleaseId,
+    User,
+    Launched,
+    Errored,
+}
@@ -0,0 +1,138 @@
+use sea_orm_migration::prelude::*;
+
+/// 新增错误按包名记录的版本表, 记录各包名已上报的最新版本及解决的版本,
+/// 替代 upload_log 中不区分包名的 resolved_version
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(LogPackageVersion::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(LogPackageVersion::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(LogPackageVersion::LogId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(LogPackageVersion::Package)
+                            .custom(Alias::new("TINYTEXT"))
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(LogPackageVersion::LatestVersion)
+                            .custom(Alias::new("TINYTEXT"))
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(LogPackageVersion::ResolvedVersion)
+                            .custom(Alias::new("TINYTEXT"))
+                            .null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(LogPackageVersion::Table, LogPackageVersion::LogId)
+                            .to(UploadLog::Table, UploadLog::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx-log_package_version-log_id-package")
+                    .table(LogPackageVersion::Table)
+                    .col(LogPackageVersion::LogId)
+                    .col(LogPackageVersion::Package)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        // 旧数据只能根据保存的上报详情生成, 最新版本取最后上报的版本
+        // (SQLite 中与 MAX 一起查询的列取自最大值所在的行);
+        // 原解决版本记录到该错误上报过的每个包名
+        let db = manager.get_connection();
+        db.execute_unprepared(
+            "INSERT INTO log_package_version (log_id, package, latest_version, resolved_version)
+            SELECT log_id, package, version, resolved_version FROM (
+                SELECT upload_occurrence.log_id, upload_user.package, upload_user.version,
+                    upload_log.resolved_version, MAX(upload_user.time)
+                FROM upload_occurrence
+                INNER JOIN upload_user ON upload_user.id = upload_occurrence.user_id
+                INNER JOIN upload_log ON upload_log.id = upload_occurrence.log_id
+                WHERE upload_user.version != ''
+                GROUP BY upload_occurrence.log_id, upload_user.package
+            )",
+        )
+        .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(UploadLog::Table)
+                    .drop_column(UploadLog::ResolvedVersion)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(UploadLog::Table)
+                    .add_column(
+                        ColumnDef::new(UploadLog::ResolvedVersion)
+                            .custom(Alias::new("TINYTEXT"))
+                            .null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        let db = manager.get_connection();
+        db.execute_unprepared(
+            "UPDATE upload_log SET resolved_version = (
+                SELECT MAX(log_package_version.resolved_version) FROM log_package_version
+                WHERE log_package_version.log_id = upload_log.id
+            )",
+        )
+        .await?;
+
+        manager
+            .drop_table(Table::drop().table(LogPackageVersion::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum UploadLog {
+    Table,
+    Id,
+    ResolvedVersion,
+}
+
+#[derive(DeriveIden)]
+enum LogPackageVersion {
+    Table,
+    Id,
+    LogId,
+    Package,
+    LatestVersion,
+    ResolvedVersion,
+}
@@ -0,0 +1,44 @@
+pub use sea_orm_migration::prelude::*;
+
+mod m20240601_000001_create_tables;
+mod m20261018_000002_create_upload_occurrence;
+mod m20261018_000003_create_project;
+mod m20261018_000004_create_account;
+mod m20261018_000005_add_configuration_json;
+mod m20261018_000006_create_statistics_device;
+mod m20261018_000007_create_normalize_rule;
+mod m20261018_000008_add_log_fingerprint;
+mod m20261018_000009_add_log_merge;
+mod m20261018_000010_create_upload_event;
+mod m20261018_000011_add_receive_time;
+mod m20261018_000012_create_sample_stratum;
+mod m20261018_000013_add_issue_status;
+mod m20261018_000014_create_package_version_scheme;
+mod m20261018_000015_create_package_release;
+mod m20261018_000016_create_log_package_version;
+
+pub struct Migrator;
+
+#[async_trait::async_trait]
+impl MigratorTrait for Migrator {
+    fn migrations() -> Vec<Box<dyn MigrationTrait>> {
+        vec![
+            Box::new(m20240601_000001_create_tables::Migration),
+            Box::new(m20261018_000002_create_upload_occurrence::Migration),
+            Box::new(m20261018_000003_create_project::Migration),
+            Box::new(m20261018_000004_create_account::Migration),
+            Box::new(m20261018_000005_add_configuration_json::Migration),
+            Box::new(m20261018_000006_create_statistics_device::Migration),
+            Box::new(m20261018_000007_create_normalize_rule::Migration),
+            Box::new(m20261018_000008_add_log_fingerprint::Migration),
+            Box::new(m20261018_000009_add_log_merge::Migration),
+            Box::new(m20261018_000010_create_upload_event::Migration),
+            Box::new(m20261018_000011_add_receive_time::Migration),
+            Box::new(m20261018_000012_create_sample_stratum::Migration),
+            Box::new(m20261018_000013_add_issue_status::Migration),
+            Box::new(m20261018_000014_create_package_version_scheme::Migration),
+            Box::new(m20261018_000015_create_package_release::Migration),
+            Box::new(m20261018_000016_create_log_package_version::Migration),
+        ]
+    }
+}
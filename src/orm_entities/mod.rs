@@ -2,6 +2,23 @@
 
 pub mod prelude;
 
+pub mod account;
+pub mod account_project;
+pub mod log_package_version;
+pub mod log_status_change;
+pub mod log_type_rule;
+pub mod normalize_rule;
+pub mod package_release;
+pub mod package_release_user;
+pub mod package_version_scheme;
+pub mod project;
+pub mod project_key;
+pub mod sample_stratum;
+pub mod sea_orm_active_enums;
+pub mod statistics_device;
+pub mod statistics_device_day;
+pub mod upload_event;
 pub mod upload_log;
+pub mod upload_occurrence;
 pub mod upload_statistics_cli_cfg;
 pub mod upload_user;
@@ -1,5 +1,23 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.12.15
 
+#![allow(unused_imports)]
+
+pub use super::account::Entity as Account;
+pub use super::account_project::Entity as AccountProject;
+pub use super::log_package_version::Entity as LogPackageVersion;
+pub use super::log_status_change::Entity as LogStatusChange;
+pub use super::log_type_rule::Entity as LogTypeRule;
+pub use super::normalize_rule::Entity as NormalizeRule;
+pub use super::package_release::Entity as PackageRelease;
+pub use super::package_release_user::Entity as PackageReleaseUser;
+pub use super::package_version_scheme::Entity as PackageVersionScheme;
+pub use super::project::Entity as Project;
+pub use super::project_key::Entity as ProjectKey;
+pub use super::sample_stratum::Entity as SampleStratum;
+pub use super::statistics_device::Entity as StatisticsDevice;
+pub use super::statistics_device_day::Entity as StatisticsDeviceDay;
+pub use super::upload_event::Entity as UploadEvent;
 pub use super::upload_log::Entity as UploadLog;
+pub use super::upload_occurrence::Entity as UploadOccurrence;
 pub use super::upload_statistics_cli_cfg::Entity as UploadStatisticsCliCfg;
 pub use super::upload_user::Entity as UploadUser;
@@ -1,5 +1,6 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.12.15
 
+use super::sea_orm_active_enums::IssueStatus;
 use sea_orm::entity::prelude::*;
 
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
@@ -9,20 +10,57 @@ pub struct Model {
     pub id: i32,
     #[sea_orm(column_type = "custom(\"TINYTEXT\")")]
     pub hash: String,
+    /// 旧版逗号分隔的用户id列表, 已迁移至 upload_occurrence
     #[sea_orm(column_type = "Text")]
     pub user_list: String,
     pub first_time: DateTime,
     pub last_time: DateTime,
     pub total_count: i32,
-    pub status: i32,
+    pub status: IssueStatus,
     pub resolution_time: DateTime,
     #[sea_orm(column_type = "custom(\"TINYTEXT\")")]
     pub log_type: String,
     #[sea_orm(column_type = "Text")]
     pub message: String,
+    pub project_id: i32,
+    /// 上报时指定的分组指纹
+    #[sea_orm(column_type = "custom(\"TINYTEXT\")", nullable)]
+    pub fingerprint: Option<String>,
+    /// 手动合并到的主错误id
+    #[sea_orm(indexed)]
+    pub merged_into: Option<i32>,
+    /// 静音截止时间
+    pub muted_until: Option<DateTime>,
+    /// 静音截止的上报次数, 累计上报次数达到该值时取消静音
+    pub muted_until_count: Option<i32>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
-pub enum Relation {}
+pub enum Relation {
+    #[sea_orm(has_many = "super::log_package_version::Entity")]
+    LogPackageVersion,
+    #[sea_orm(has_many = "super::log_status_change::Entity")]
+    LogStatusChange,
+    #[sea_orm(has_many = "super::upload_occurrence::Entity")]
+    UploadOccurrence,
+}
+
+impl Related<super::log_package_version::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::LogPackageVersion.def()
+    }
+}
+
+impl Related<super::log_status_change::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::LogStatusChange.def()
+    }
+}
+
+impl Related<super::upload_occurrence::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::UploadOccurrence.def()
+    }
+}
 
 impl ActiveModelBehavior for ActiveModel {}
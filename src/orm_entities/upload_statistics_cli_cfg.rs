@@ -20,6 +20,14 @@ pub struct Model {
     #[sea_orm(column_type = "custom(\"TINYTEXT\")")]
     pub region: String,
     pub time: DateTime,
+    pub project_id: i32,
+    pub configuration_json: Json,
+    #[sea_orm(column_type = "custom(\"TINYTEXT\")")]
+    pub device_id: String,
+    pub receive_time: DateTime,
+    pub clock_skew: Option<i64>,
+    #[sea_orm(column_type = "custom(\"TINYTEXT\")")]
+    pub version: String,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -20,9 +20,20 @@ pub struct Model {
     #[sea_orm(column_type = "custom(\"TINYTEXT\")")]
     pub ip: String,
     pub time: DateTime,
+    pub receive_time: DateTime,
+    pub clock_skew: Option<i64>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
-pub enum Relation {}
+pub enum Relation {
+    #[sea_orm(has_many = "super::upload_occurrence::Entity")]
+    UploadOccurrence,
+}
+
+impl Related<super::upload_occurrence::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::UploadOccurrence.def()
+    }
+}
 
 impl ActiveModelBehavior for ActiveModel {}